@@ -142,12 +142,12 @@ defmodule SettleIt.GameServer.Engine do
   end
 
   def move_player(%State.Game{bodies: bodies} = state, player_id, %{
-        x: x,
-        y: y
+        dx: dx,
+        dy: dy
       }) do
     body = Map.get(bodies, player_id)
 
-    msg = %{action: "move", x: x, y: y, id: body.id}
+    msg = %{action: "move", direction: {dx / 1, dy / 1}, id: body.id}
 
     send_physics_message(
       state,
@@ -166,11 +166,21 @@ defmodule SettleIt.GameServer.Engine do
     state
   end
 
+  def aim_player(%State.Game{bodies: bodies} = state, player_id, %{yaw: yaw, pitch: pitch}) do
+    body = Map.get(bodies, player_id)
+
+    # the physics process clamps the pitch and turns the body to the yaw
+    msg = %{action: "aim", id: body.id, yaw: yaw / 1, pitch: pitch / 1}
+    send_physics_message(state, msg)
+
+    state
+  end
+
   def jump_player(%State.Game{bodies: bodies} = state, player_id) do
-    body = bodies |> Map.get(player_id) |> apply_jump()
-    {_linvelx, _linvely, linvelz} = body.linvel
+    body = Map.get(bodies, player_id)
 
-    msg = %{action: "jump", id: body.id, linvel_z: linvelz}
+    # the physics process decides whether the player is grounded and can jump
+    msg = %{action: "jump", id: body.id}
     send_physics_message(state, msg)
 
     state
@@ -264,19 +274,4 @@ defmodule SettleIt.GameServer.Engine do
     msg = Map.put(body, :action, "add_player")
     send_physics_message(state, msg)
   end
-
-  defp apply_jump(player_body) do
-    if can_jump?(player_body) do
-      {linvelx, linvely, _linvelz} = player_body.linvel
-
-      %{player_body | linvel: {linvelx, linvely, 10.0}}
-    else
-      player_body
-    end
-  end
-
-  defp can_jump?(%{linvel: {_x, _y, linvelz}}) when linvelz < 0.0001 and linvelz > -0.0001,
-    do: true
-
-  defp can_jump?(_body), do: false
 end
@@ -2,7 +2,9 @@ defmodule SettleIt.GameServer.Engine.Message do
   require Logger
   alias SettleIt.GameServer.State
 
-  @spec decode(msg :: String.t()) :: :game_won | {bodies :: [%State.Body{}], extra :: String.t()}
+  # bodies removed from the world for good are marked `:removed` under their id
+  @spec decode(msg :: String.t()) ::
+          :game_won | {bodies :: %{String.t() => %State.Body{} | :removed}, extra :: String.t()}
   def decode(msg) do
     msg
     |> String.split("\n")
@@ -49,6 +51,16 @@ defmodule SettleIt.GameServer.Engine.Message do
     :game_won
   end
 
+  # destroyed obstacles are never sent again, so their removal is passed on in their place
+  defp decode_bodies(%{"event" => "body_removed", "id" => id}) do
+    %{id => :removed}
+  end
+
+  # other game events (hits, eliminations, match results) are not bodies
+  defp decode_bodies(%{"event" => _event}) do
+    %{}
+  end
+
   defp decode_bodies(raw_bodies) do
     raw_bodies
     |> Enum.map(fn raw_body -> {raw_body["id"], decode_body(raw_body)} end)
@@ -67,7 +79,12 @@ defmodule SettleIt.GameServer.Engine.Message do
       angvel: decode_vec3(raw_body, "angvel"),
       dimensions: decode_vec3(raw_body, "dimensions"),
       mass: raw_body["mass"],
-      hp: raw_body["hp"]
+      hp: raw_body["hp"],
+      ammo: raw_body["ammo"],
+      buffs: raw_body["buffs"] || [],
+      movement: raw_body["movement"],
+      aim: raw_body["aim"],
+      shape: raw_body["shape"]
     }
   end
 
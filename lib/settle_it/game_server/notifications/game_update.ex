@@ -52,7 +52,12 @@ defmodule SettleIt.GameServer.Notifications.GameUpdate do
       d: encode_vec3(body.dimensions),
       m: body.mass,
       cl: body.class,
-      hp: body.hp
+      hp: body.hp,
+      am: body.ammo,
+      bf: body.buffs,
+      mv: body.movement,
+      aim: body.aim,
+      sh: body.shape
     }
   end
 
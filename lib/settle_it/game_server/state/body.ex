@@ -10,5 +10,10 @@ defmodule SettleIt.GameServer.State.Body do
             angvel: {0.0, 0.0, 0.0},
             dimensions: {0.0, 0.0, 0.0},
             mass: 0.0,
-            hp: 0
+            hp: 0,
+            ammo: nil,
+            buffs: [],
+            movement: nil,
+            aim: nil,
+            shape: nil
 end
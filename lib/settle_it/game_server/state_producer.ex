@@ -43,13 +43,20 @@ defmodule SettleIt.GameServer.StateProducer do
         next_game_state = %{state | status: :finished}
         {:noreply, [{:state_update, next_game_state}], next_game_state}
 
-      {new_bodies, extra_data} ->
+      {decoded_bodies, extra_data} ->
+        {removed_bodies, new_bodies} =
+          Enum.split_with(decoded_bodies, fn {_k, body} -> body == :removed end)
+
+        removed_ids = Enum.map(removed_bodies, fn {id, _removed} -> id end)
+        new_bodies = Map.new(new_bodies)
+
         new_world_bodies =
           new_bodies |> Enum.filter(fn {_k, body} -> body.class == "obstacle" end) |> Map.new()
 
+        # fixed obstacles are only sent with the first frame, so destroyed ones are dropped here
         world_bodies =
           if new_world_bodies == %{} do
-            state.world_bodies
+            Map.drop(state.world_bodies, removed_ids)
           else
             new_world_bodies
           end
@@ -62,6 +69,7 @@ defmodule SettleIt.GameServer.StateProducer do
           |> Enum.reject(fn {_k, body} -> body.class == "bullet" end)
           |> Map.new()
           |> Map.merge(other_bodies)
+          |> Map.drop(removed_ids)
 
         status =
           if new_world_bodies !== %{} do
@@ -84,7 +92,11 @@ defmodule SettleIt.GameServer.StateProducer do
             state.status !== next_game_state.status ->
               [{:state_update, next_game_state}]
 
-            new_bodies !== %{} ->
+            # bodies updates leave the world bodies out, so only a full update removes cover
+            world_bodies !== state.world_bodies ->
+              [{:state_update, next_game_state}]
+
+            new_bodies !== %{} or removed_ids !== [] ->
               [{:bodies_update, next_game_state}]
 
             true ->
@@ -151,10 +163,10 @@ defmodule SettleIt.GameServer.StateProducer do
   end
 
   defp apply_action(
-         {:player_move, player_id, coords},
+         {:player_move_direction, player_id, direction},
          state
        ) do
-    {Engine.move_player(state, player_id, coords), nil}
+    {Engine.move_player(state, player_id, direction), nil}
   end
 
   defp apply_action(
@@ -164,6 +176,13 @@ defmodule SettleIt.GameServer.StateProducer do
     {Engine.rotate_player(state, player_id, angle), nil}
   end
 
+  defp apply_action(
+         {:player_aim, player_id, aim},
+         state
+       ) do
+    {Engine.aim_player(state, player_id, aim), nil}
+  end
+
   defp apply_action(
          {:player_jump, player_id},
          state
@@ -1,4 +1,14 @@
 defmodule SettleItWeb.GameChannel do
+  @moduledoc """
+  The channel a player's client plays a game through.
+
+  Movement is sent as `player_move_direction` with `dx` and `dy`, the direction the player wants
+  to move in. Vectors longer than 1 are scaled down to full speed by the physics engine, and
+  `{0, 0}` stops the player. Positions are never sent: the server decides where players are.
+
+  Looking around is sent as `player_aim` with `yaw` and `pitch` in degrees. The pitch is clamped
+  to the configured limit, and body updates report the result as `aim`.
+  """
   require Logger
   use SettleItWeb, :channel
 
@@ -85,15 +95,25 @@ defmodule SettleItWeb.GameChannel do
   end
 
   def handle_in(
-        "player_move",
-        %{"player_id" => player_id, "x" => x, "y" => y},
+        "player_move_direction",
+        %{"player_id" => player_id, "dx" => dx, "dy" => dy},
         socket
-      ) do
-    notify_game_server(socket, {:player_move, player_id, %{x: x, y: y}})
+      )
+      when is_number(dx) and is_number(dy) do
+    notify_game_server(socket, {:player_move_direction, player_id, %{dx: dx, dy: dy}})
 
     {:noreply, socket}
   end
 
+  def handle_in("player_move_direction", _params, socket) do
+    {:reply, {:error, %{reason: "dx and dy must be numbers"}}, socket}
+  end
+
+  # player_move used to take an absolute position, which would now be read as a direction
+  def handle_in("player_move", _params, socket) do
+    {:reply, {:error, %{reason: "player_move was replaced by player_move_direction"}}, socket}
+  end
+
   def handle_in(
         "player_rotate",
         %{"player_id" => player_id, "angle" => angle},
@@ -104,6 +124,21 @@ defmodule SettleItWeb.GameChannel do
     {:noreply, socket}
   end
 
+  def handle_in(
+        "player_aim",
+        %{"player_id" => player_id, "yaw" => yaw, "pitch" => pitch},
+        socket
+      )
+      when is_number(yaw) and is_number(pitch) do
+    notify_game_server(socket, {:player_aim, player_id, %{yaw: yaw, pitch: pitch}})
+
+    {:noreply, socket}
+  end
+
+  def handle_in("player_aim", _params, socket) do
+    {:reply, {:error, %{reason: "yaw and pitch must be numbers"}}, socket}
+  end
+
   def handle_in(
         "player_jump",
         %{"player_id" => player_id},
@@ -0,0 +1,130 @@
+//! How long a tick takes in a full arena of 500 obstacles, for a range of player and bullet
+//! counts, and how long writing the resulting body updates takes. Anything approaching the tick
+//! length (about 16.7ms at the default rate) no longer fits in the port loop.
+//!
+//! The scales can be overridden with comma separated lists, e.g.
+//! `TICK_BENCH_PLAYERS=8,64 TICK_BENCH_BULLETS=0,500 cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use physics::body;
+use physics::config::Config;
+use physics::game::{self, Game};
+use physics::user_input::{self, UserInput};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const SEED: u64 = 1;
+const DEFAULT_PLAYERS: &[usize] = &[2, 16, 64];
+const DEFAULT_BULLETS: &[usize] = &[0, 100, 500];
+// enough for the players to drop onto the floor and settle before anything is measured
+const SETTLE_TICKS: u64 = 30;
+const BULLET_SPEED: f32 = 40.0;
+const BULLET_HEIGHT: f32 = 1.5;
+// bullets are spread over the same square as the arena's obstacles
+const BULLET_SPREAD: f32 = 75.0;
+
+fn get_scales(variable: &str, default: &[usize]) -> Vec<usize> {
+    match std::env::var(variable) {
+        Ok(scales) => scales
+            .split(',')
+            .map(|scale| scale.trim().parse().expect("Invalid scale"))
+            .collect(),
+        Err(_) => default.to_vec(),
+    }
+}
+
+fn apply(game_state: &mut Game, line: &str) {
+    let input = user_input::parse_user_input(line)
+        .unwrap_or_else(|err| panic!("Failed to parse {}: {}", line, err));
+    user_input::apply_user_input(&[input], game_state);
+}
+
+/// A generated arena with the given number of players split over two teams, and bullets that have
+/// just been fired across it. Bots stand in for the players since they are given free spawn
+/// points. Rather than being asked for inputs, each walks in its own direction, so that every
+/// step moves every character controller the way a match full of players would.
+fn build_scene(players: usize, bullets: usize) -> Game {
+    let mut game_state = game::init(&Config::default(), SEED);
+    for player in 0..players {
+        let team_id = if player % 2 == 0 { "red" } else { "blue" };
+        apply(
+            &mut game_state,
+            &format!(r#"{{"action":"add_bot","team_id":"{}"}}"#, team_id),
+        );
+    }
+    for _ in 0..SETTLE_TICKS {
+        game::step(&mut game_state);
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(SEED);
+    // bots are numbered from 1 in the order they were added
+    for bot in 1..=players {
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        game::move_body(
+            &mut game_state,
+            &format!("bot-{}", bot),
+            (angle.cos(), angle.sin()),
+        );
+    }
+    for bullet in 0..bullets {
+        let x = rng.gen_range(-BULLET_SPREAD..BULLET_SPREAD);
+        let y = rng.gen_range(-BULLET_SPREAD..BULLET_SPREAD);
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let body = body::create_bullet(
+            &format!("bullet-{}", bullet),
+            "turret",
+            Some("turret"),
+            (x, y, BULLET_HEIGHT),
+            (BULLET_SPEED * angle.cos(), BULLET_SPEED * angle.sin(), 0.0),
+        );
+        user_input::apply_user_input(&[UserInput::Shoot { body }], &mut game_state);
+    }
+    game_state
+}
+
+fn bench_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.sample_size(20);
+    for players in get_scales("TICK_BENCH_PLAYERS", DEFAULT_PLAYERS) {
+        for bullets in get_scales("TICK_BENCH_BULLETS", DEFAULT_BULLETS) {
+            // every sample steps the same tick, restored from a snapshot so that bullets are still
+            // in flight rather than spent on the walls by earlier samples
+            let snapshot = game::snapshot(&build_scene(players, bullets)).unwrap();
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_players", players), bullets),
+                &snapshot,
+                |b, snapshot| {
+                    b.iter_batched(
+                        || game::restore(snapshot).unwrap(),
+                        |mut game_state| {
+                            game::step(&mut game_state);
+                            game_state
+                        },
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_write_body_updates(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write_body_updates");
+    for players in get_scales("TICK_BENCH_PLAYERS", DEFAULT_PLAYERS) {
+        let game_state = build_scene(players, 0);
+        // the first tick of a match writes every body, which is the most there is to write
+        let handles = game::get_handles(&game_state);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}_players", players)),
+            &handles,
+            |b, handles| {
+                b.iter(|| physics::write_body_updates(handles, &game_state, &mut std::io::sink()))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_step, bench_write_body_updates);
+criterion_main!(benches);
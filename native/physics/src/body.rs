@@ -1,7 +1,11 @@
+use crate::movement;
+use crate::pickup;
+use crate::rotation::EulerDegrees;
+use crate::rules::BodyRegion;
+use crate::shape::{self, ShapeDescriptor};
 use crate::util::to_vec3;
-use rapier3d::na::Vector3;
+use rapier3d::parry::query;
 use rapier3d::prelude::*;
-use serde;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -11,7 +15,7 @@ pub struct Body {
     pub team_id: Option<String>,
     pub owner_id: Option<String>,
     pub translation: (f32, f32, f32),
-    pub rotation: (f32, f32, f32),
+    pub rotation: EulerDegrees,
     pub linvel: (f32, f32, f32),
     pub angvel: (f32, f32, f32),
     pub dimensions: (f32, f32, f32),
@@ -19,115 +23,451 @@ pub struct Body {
     #[serde(rename = "class")]
     pub class: BodyClass,
     pub hp: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pickup: Option<pickup::Pickup>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buffs: Vec<pickup::Buff>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ammo: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub movement: Option<movement::MovementStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aim: Option<movement::Aim>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<ShapeDescriptor>,
+    // obstacles only: bullets wear the obstacle's hp down and it is removed at zero
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub destructible: bool,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "snake_case")]
 pub enum BodyClass {
     Player,
     Bullet,
     Obstacle,
+    Pickup,
+    // loose objects such as crates and barrels that players can push around
+    Prop,
     Test,
 }
 
-pub fn overlaps_existing_bodies(body: &Body, bodies: &Vec<Body>) -> bool {
-    let mut overlaps = false;
-    for current_body in bodies {
-        if bounding_boxes_overlap(body, &current_body) {
-            overlaps = true;
-            break;
+pub fn overlaps_existing_bodies(body: &Body, bodies: &[Body]) -> bool {
+    bodies
+        .iter()
+        .any(|current_body| shapes_overlap(body, current_body))
+}
+
+// bodies resting flush against each other, like obstacles on the floor, are not overlapping
+const OVERLAP_TOLERANCE: f32 = 0.001;
+
+/// Tests the bodies' actual (possibly rotated) shapes against each other, with a cheap bounding
+/// sphere check first since most pairs are nowhere near each other.
+fn shapes_overlap(a: &Body, b: &Body) -> bool {
+    let distance = (to_vec3(a.translation) - to_vec3(b.translation)).norm();
+    if distance > get_bounding_radius(a) + get_bounding_radius(b) {
+        return false;
+    }
+
+    let (a_shape_position, a_shape) = get_shape(a);
+    let (b_shape_position, b_shape) = get_shape(b);
+    let a_position = get_position(a) * a_shape_position;
+    let b_position = get_position(b) * b_shape_position;
+
+    match query::contact(&a_position, &*a_shape, &b_position, &*b_shape, 0.0) {
+        Ok(Some(contact)) => contact.dist < -OVERLAP_TOLERANCE,
+        Ok(None) => false,
+        // unsupported shape pairs, such as a heightfield and a box, fall back to comparing bounding boxes
+        Err(_) => {
+            let a_aabb = a_shape.compute_aabb(&a_position);
+            let b_aabb = b_shape.compute_aabb(&b_position);
+            a_aabb
+                .intersection(&b_aabb)
+                .is_some_and(|overlap| overlap.extents().min() > OVERLAP_TOLERANCE)
         }
     }
+}
+
+/// The radius of a sphere around the body's centre that holds all of it.
+pub fn get_bounding_radius(body: &Body) -> f32 {
+    match &body.shape {
+        Some(descriptor) => shape::get_bounding_radius(descriptor),
+        None => to_vec3(body.dimensions).norm() / 2.0,
+    }
+}
+
+fn get_position(body: &Body) -> Isometry<f32> {
+    Isometry::new(
+        to_vec3(body.translation),
+        body.rotation.to_quaternion().scaled_axis(),
+    )
+}
+
+pub const PLAYER_COLLIDER_RADIUS: f32 = 0.525;
+// players and bullets are the size the Elixir engine makes them
+pub const PLAYER_HEIGHT: f32 = 2.0;
+const PLAYER_MASS: f32 = 100.0;
+const BULLET_SIZE: f32 = 0.1;
+const BULLET_MASS: f32 = 0.05;
+
+/// A player built the way the Elixir engine builds them, as its own owner.
+pub fn create_player(id: &str, team_id: &str, translation: (f32, f32, f32), hp: i32) -> Body {
+    Body {
+        id: id.to_string(),
+        team_id: Some(team_id.to_string()),
+        owner_id: Some(id.to_string()),
+        translation,
+        rotation: EulerDegrees::default(),
+        linvel: (0.0, 0.0, 0.0),
+        angvel: (0.0, 0.0, 0.0),
+        dimensions: (0.0, PLAYER_COLLIDER_RADIUS, PLAYER_HEIGHT),
+        mass: PLAYER_MASS,
+        class: BodyClass::Player,
+        hp,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: None,
+        destructible: false,
+    }
+}
+
+/// A bullet built the way the Elixir engine builds them, leaving `translation` at `linvel`.
+pub fn create_bullet(
+    id: &str,
+    owner_id: &str,
+    team_id: Option<&str>,
+    translation: (f32, f32, f32),
+    linvel: (f32, f32, f32),
+) -> Body {
+    Body {
+        id: id.to_string(),
+        team_id: team_id.map(str::to_string),
+        owner_id: Some(owner_id.to_string()),
+        translation,
+        rotation: EulerDegrees::default(),
+        linvel,
+        angvel: (0.0, 0.0, 0.0),
+        dimensions: (BULLET_SIZE, BULLET_SIZE, BULLET_SIZE),
+        mass: BULLET_MASS,
+        class: BodyClass::Bullet,
+        hp: 0,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: None,
+        destructible: false,
+    }
+}
+
+/// Classifies a hit on a player capsule: the upper hemisphere is the head, the lower hemisphere
+/// the legs and the cylindrical section in between the torso.
+pub fn get_hit_region(player: &Body, hit_point: (f32, f32, f32)) -> BodyRegion {
+    let half_segment = player.dimensions.2 / 2.0 - PLAYER_COLLIDER_RADIUS;
+    let relative_z = hit_point.2 - player.translation.2;
+
+    if relative_z > half_segment {
+        BodyRegion::Head
+    } else if relative_z < -half_segment {
+        BodyRegion::Legs
+    } else {
+        BodyRegion::Torso
+    }
+}
+
+const WORLD_GROUP: Group = Group::GROUP_1;
+const PLAYER_GROUP: Group = Group::GROUP_2;
+const BULLET_GROUP: Group = Group::GROUP_3;
+const GHOST_GROUP: Group = Group::GROUP_4;
+const PICKUP_GROUP: Group = Group::GROUP_5;
+const PROP_GROUP: Group = Group::GROUP_6;
+
+pub fn get_collision_groups(class: BodyClass) -> InteractionGroups {
+    match class {
+        BodyClass::Player => InteractionGroups::new(PLAYER_GROUP, Group::ALL),
+        BodyClass::Bullet => InteractionGroups::new(BULLET_GROUP, Group::ALL),
+        BodyClass::Test => InteractionGroups::new(WORLD_GROUP, Group::ALL),
+        BodyClass::Obstacle => InteractionGroups::new(WORLD_GROUP, Group::ALL),
+        BodyClass::Pickup => InteractionGroups::new(PICKUP_GROUP, PLAYER_GROUP),
+        BodyClass::Prop => InteractionGroups::new(PROP_GROUP, Group::ALL),
+    }
+}
+
+/// Dead players only rest on the world geometry: bullets and other players pass through them.
+pub fn get_ghost_collision_groups() -> InteractionGroups {
+    InteractionGroups::new(GHOST_GROUP, WORLD_GROUP)
+}
+
+/// What blocks a line of sight: the world, props and living players, but not bullets, pickups or
+/// the dead.
+pub fn get_sight_groups() -> InteractionGroups {
+    InteractionGroups::new(Group::ALL, WORLD_GROUP | PLAYER_GROUP | PROP_GROUP)
+}
+
+pub fn is_dead_player(body: &Body) -> bool {
+    matches!((body.class, body.hp), (BodyClass::Player, 0))
+}
+
+/// Returns the capsule for a player of the given total height, standing along the z axis.
+pub fn get_player_shape(height: f32) -> SharedShape {
+    let half_segment = (height / 2.0 - PLAYER_COLLIDER_RADIUS).max(0.0);
+    SharedShape::capsule_z(half_segment, PLAYER_COLLIDER_RADIUS)
+}
+
+/// Returns the body's shape along with the shape's pose relative to the body.
+pub fn get_shape(body: &Body) -> (Isometry<f32>, SharedShape) {
+    if let Some(shape) = body.shape.as_ref().and_then(shape::to_shared_shape) {
+        return shape;
+    }
 
-    overlaps
-}
-
-fn bounding_boxes_overlap(a: &Body, b: &Body) -> bool {
-    let (ax_min, ax_max) = (
-        a.translation.0 - a.dimensions.0 / 2.0,
-        a.translation.0 + a.dimensions.0 / 2.0,
-    );
-    let (bx_min, bx_max) = (
-        b.translation.0 - b.dimensions.0 / 2.0,
-        b.translation.0 + b.dimensions.0 / 2.0,
-    );
-    let (ay_min, ay_max) = (
-        a.translation.1 - a.dimensions.1 / 2.0,
-        a.translation.1 + a.dimensions.1 / 2.0,
-    );
-    let (by_min, by_max) = (
-        b.translation.1 - b.dimensions.1 / 2.0,
-        b.translation.1 + b.dimensions.1 / 2.0,
-    );
-    let (az_min, az_max) = (
-        a.translation.2 - a.dimensions.2 / 2.0,
-        a.translation.2 + a.dimensions.2 / 2.0,
-    );
-    let (bz_min, bz_max) = (
-        b.translation.2 - b.dimensions.2 / 2.0,
-        b.translation.2 + b.dimensions.2 / 2.0,
-    );
-
-    ax_min < bx_max
-        && ax_max > bx_min
-        && ay_min < by_max
-        && ay_max > by_min
-        && az_min < bz_max
-        && az_max > bz_min
-}
-
-const PLAYER_COLLIDER_RADIUS: f32 = 0.525;
-pub fn get_collider(body: &Body) -> Collider {
     let half_height = body.dimensions.2 / 2.0;
+    let shape = match body.class {
+        BodyClass::Player => get_player_shape(body.dimensions.2),
+        BodyClass::Bullet | BodyClass::Test => SharedShape::ball(half_height),
+        BodyClass::Obstacle | BodyClass::Pickup | BodyClass::Prop => SharedShape::cuboid(
+            body.dimensions.0 / 2.0,
+            body.dimensions.1 / 2.0,
+            half_height,
+        ),
+    };
+
+    (Isometry::identity(), shape)
+}
+
+pub fn get_collider(body: &Body) -> Collider {
+    let collision_groups = if is_dead_player(body) {
+        get_ghost_collision_groups()
+    } else {
+        get_collision_groups(body.class)
+    };
+
+    let (shape_position, shape) = get_shape(body);
+
     match body.class {
-        BodyClass::Player => {
-            ColliderBuilder::capsule_z(half_height - PLAYER_COLLIDER_RADIUS, PLAYER_COLLIDER_RADIUS)
-                .active_events(ActiveEvents::COLLISION_EVENTS)
-                .build()
-        }
-        BodyClass::Bullet => ColliderBuilder::new(SharedShape::ball(half_height))
+        BodyClass::Player | BodyClass::Bullet => ColliderBuilder::new(shape)
+            .position(shape_position)
             .active_events(ActiveEvents::COLLISION_EVENTS)
+            .collision_groups(collision_groups)
+            .build(),
+        BodyClass::Test | BodyClass::Obstacle | BodyClass::Prop => ColliderBuilder::new(shape)
+            .position(shape_position)
+            .collision_groups(collision_groups)
+            .build(),
+        BodyClass::Pickup => ColliderBuilder::new(shape)
+            .position(shape_position)
+            .sensor(true)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
+            .active_collision_types(ActiveCollisionTypes::all())
+            .collision_groups(collision_groups)
             .build(),
-        BodyClass::Test => ColliderBuilder::new(SharedShape::ball(half_height)).build(),
-        BodyClass::Obstacle => ColliderBuilder::new(SharedShape::cuboid(
-            body.dimensions.0 / 2.0,
-            body.dimensions.1 / 2.0,
-            body.dimensions.2 / 2.0,
-        ))
-        .build(),
     }
 }
 
 pub fn to_rigid_body(body: &Body) -> RigidBody {
     match body.class {
-        BodyClass::Player => to_dynamic_rigid_body(body),
+        BodyClass::Player => to_kinematic_rigid_body(body),
         BodyClass::Bullet => to_dynamic_rigid_body(body),
         BodyClass::Test => to_dynamic_rigid_body(body),
         BodyClass::Obstacle => to_static_rigid_body(body),
+        BodyClass::Pickup => to_static_rigid_body(body),
+        BodyClass::Prop => to_prop_rigid_body(body),
     }
 }
 
 fn to_dynamic_rigid_body(body: &Body) -> RigidBody {
     RigidBodyBuilder::new(RigidBodyType::Dynamic)
         .translation(to_vec3(body.translation))
-        .rotation(to_vec3(body.rotation))
+        .rotation(body.rotation.to_quaternion().scaled_axis())
         .lock_rotations()
         .linvel(to_vec3(body.linvel))
         .angvel(to_vec3(body.angvel))
         .additional_mass(body.mass)
-        .sleeping(match (body.class, body.hp) {
-            (BodyClass::Player, 0) => true,
-            _ => false,
-        })
         .ccd_enabled(true)
         .build()
 }
 
+// players are moved by the character controller rather than by forces
+fn to_kinematic_rigid_body(body: &Body) -> RigidBody {
+    RigidBodyBuilder::new(RigidBodyType::KinematicPositionBased)
+        .translation(to_vec3(body.translation))
+        .rotation(body.rotation.to_quaternion().scaled_axis())
+        .lock_rotations()
+        .additional_mass(body.mass)
+        .build()
+}
+
+const PROP_LINEAR_DAMPING: f32 = 0.5;
+const PROP_ANGULAR_DAMPING: f32 = 0.5;
+
+// props tumble freely, and ones placed at rest start asleep so they cost nothing until disturbed
+fn to_prop_rigid_body(body: &Body) -> RigidBody {
+    let linvel = to_vec3(body.linvel);
+    let angvel = to_vec3(body.angvel);
+    RigidBodyBuilder::new(RigidBodyType::Dynamic)
+        .translation(to_vec3(body.translation))
+        .rotation(body.rotation.to_quaternion().scaled_axis())
+        .linvel(linvel)
+        .angvel(angvel)
+        .additional_mass(body.mass)
+        .linear_damping(PROP_LINEAR_DAMPING)
+        .angular_damping(PROP_ANGULAR_DAMPING)
+        .can_sleep(true)
+        .sleeping(linvel == Vector::zeros() && angvel == Vector::zeros())
+        .build()
+}
+
 fn to_static_rigid_body(body: &Body) -> RigidBody {
     RigidBodyBuilder::new(RigidBodyType::Fixed)
         .translation(to_vec3(body.translation))
-        .rotation(Vector3::z() * body.rotation.2)
+        .rotation(body.rotation.to_quaternion().scaled_axis())
         .lock_rotations()
         .additional_mass(body.mass)
         .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn parse_body(class: &str, rotation: (f32, f32, f32)) -> Body {
+        let json = format!(
+            r#"{{"id":"body","team_id":null,"owner_id":null,"translation":[0.0,0.0,1.0],
+                "rotation":[{},{},{}],"linvel":[0.0,0.0,0.0],"angvel":[0.0,0.0,0.0],
+                "dimensions":[1.0,1.0,2.0],"mass":1.0,"class":"{}","hp":1}}"#,
+            rotation.0, rotation.1, rotation.2, class
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn rigid_bodies_keep_the_rotation_they_were_built_with() {
+        for class in ["player", "bullet", "obstacle", "pickup", "test"] {
+            let body = parse_body(class, (0.0, 0.0, 135.0));
+            let rigid_body = to_rigid_body(&body);
+            let rotation = EulerDegrees::from_quaternion(rigid_body.rotation());
+
+            assert!(
+                (rotation.yaw() - 135.0).abs() < 1e-3,
+                "{} came back with yaw {}",
+                class,
+                rotation.yaw()
+            );
+        }
+    }
+
+    #[test]
+    fn tilted_static_bodies_keep_their_full_rotation() {
+        let body = parse_body("obstacle", (15.0, -20.0, 200.0));
+        let rigid_body = to_rigid_body(&body);
+
+        assert!(
+            rigid_body
+                .rotation()
+                .angle_to(&body.rotation.to_quaternion())
+                < 1e-3
+        );
+    }
+
+    fn obstacle_at(translation: (f32, f32, f32), dimensions: (f32, f32, f32), yaw: f32) -> Body {
+        let mut body = parse_body("obstacle", (0.0, 0.0, yaw));
+        body.translation = translation;
+        body.dimensions = dimensions;
+        body
+    }
+
+    #[test]
+    fn rotated_obstacles_only_overlap_where_their_shapes_do() {
+        let diagonal = obstacle_at((0.0, 0.0, 1.0), (6.0, 0.5, 2.0), 45.0);
+        // inside the diagonal plank's bounding box, but clear of the plank itself
+        let beside = obstacle_at((2.0, -2.0, 1.0), (0.5, 0.5, 2.0), 0.0);
+        let across = obstacle_at((0.0, 0.0, 1.0), (6.0, 0.5, 2.0), 135.0);
+
+        let existing = [diagonal];
+
+        assert!(!overlaps_existing_bodies(&beside, &existing));
+        assert!(overlaps_existing_bodies(&across, &existing));
+    }
+
+    #[test]
+    fn bodies_resting_on_each_other_do_not_overlap() {
+        let floor = obstacle_at((0.0, 0.0, -0.5), (10.0, 10.0, 1.0), 0.0);
+        let crate_on_floor = obstacle_at((0.0, 0.0, 1.0), (1.0, 1.0, 2.0), 30.0);
+
+        assert!(!overlaps_existing_bodies(&crate_on_floor, &[floor]));
+    }
+
+    // a 2m patch of terrain rising to 0.2m, which contact queries do not support against boxes
+    fn terrain_patch() -> Body {
+        let mut body = obstacle_at((0.0, 0.0, 0.0), (2.0, 2.0, 0.2), 0.0);
+        body.shape = Some(ShapeDescriptor::Heightfield {
+            heights: vec![vec![0.0, 0.1], vec![0.2, 0.0]],
+            size: (2.0, 2.0),
+        });
+        body
+    }
+
+    #[test]
+    fn unsupported_shape_pairs_overlap_where_their_bounding_boxes_do() {
+        let existing = [terrain_patch()];
+        let sunk_in = obstacle_at((0.0, 0.0, 0.4), (1.0, 1.0, 1.0), 0.0);
+        let resting_on_top = obstacle_at((0.0, 0.0, 0.7), (1.0, 1.0, 1.0), 0.0);
+        let above = obstacle_at((0.0, 0.0, 1.0), (1.0, 1.0, 1.0), 0.0);
+
+        assert!(overlaps_existing_bodies(&sunk_in, &existing));
+        assert!(!overlaps_existing_bodies(&resting_on_top, &existing));
+        assert!(!overlaps_existing_bodies(&above, &existing));
+    }
+
+    fn box_strategy() -> impl Strategy<Value = Body> {
+        (
+            (-10.0f32..10.0, -10.0f32..10.0, 0.0f32..5.0),
+            (0.2f32..5.0, 0.2f32..5.0, 0.2f32..5.0),
+            0.0f32..360.0,
+        )
+            .prop_map(|(translation, dimensions, yaw)| obstacle_at(translation, dimensions, yaw))
+    }
+
+    proptest! {
+        #[test]
+        fn overlapping_is_symmetric(a in box_strategy(), b in box_strategy()) {
+            prop_assert_eq!(
+                overlaps_existing_bodies(&a, std::slice::from_ref(&b)),
+                overlaps_existing_bodies(&b, std::slice::from_ref(&a))
+            );
+        }
+
+        #[test]
+        fn a_body_overlaps_anything_sharing_its_centre(a in box_strategy(), b in box_strategy()) {
+            let mut b = b;
+            b.translation = a.translation;
+
+            prop_assert!(overlaps_existing_bodies(&a, &[b]));
+        }
+
+        #[test]
+        fn bodies_beyond_each_others_reach_do_not_overlap(
+            a in box_strategy(),
+            b in box_strategy(),
+            angle in 0.0f32..std::f32::consts::TAU,
+        ) {
+            let mut b = b;
+            let reach = get_bounding_radius(&a) + get_bounding_radius(&b) + 0.01;
+            b.translation = (
+                a.translation.0 + reach * angle.cos(),
+                a.translation.1 + reach * angle.sin(),
+                a.translation.2,
+            );
+
+            prop_assert!(!overlaps_existing_bodies(&a, &[b]));
+        }
+    }
+}
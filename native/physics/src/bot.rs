@@ -0,0 +1,421 @@
+use crate::body;
+use crate::user_input::UserInput;
+use crate::util::Fnv1a;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rapier3d::na::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+// bots are built like the players the Elixir engine adds
+pub const BOT_HEIGHT: f32 = body::PLAYER_HEIGHT;
+const BOT_HP: i32 = 10;
+
+const BULLET_SPEED: f32 = 40.0;
+// far enough along the aim that a new bullet starts clear of the shooter's capsule
+const MUZZLE_DISTANCE: f32 = 1.0;
+
+// how far ahead a wandering bot looks for something in its way, and the heights above its feet
+// it looks at: blocked at the knee but clear at the chest is low enough to jump over
+pub const PROBE_DISTANCE: f32 = 2.0;
+pub const KNEE_HEIGHT: f32 = 0.5;
+pub const CHEST_HEIGHT: f32 = 1.5;
+// wandering bots turn back towards the centre rather than walking into the arena's walls
+const WANDER_LIMIT: f32 = 90.0;
+const MIN_WANDER_TICKS: u32 = 60;
+const MAX_WANDER_TICKS: u32 = 180;
+// a bot heading for somewhere picks somewhere else if it has not got there in this long
+const MAX_ROUTE_TICKS: u32 = 1200;
+// how close a bot has to come to a waypoint before making for the next one
+const WAYPOINT_RADIUS: f32 = 0.75;
+// bots close in on a target until this distance, then strafe
+const ENGAGE_DISTANCE: f32 = 15.0;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn aim_error_degrees(self) -> f32 {
+        match self {
+            Difficulty::Easy => 8.0,
+            Difficulty::Normal => 4.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    fn ticks_between_shots(self) -> u32 {
+        match self {
+            Difficulty::Easy => 45,
+            Difficulty::Normal => 30,
+            Difficulty::Hard => 15,
+        }
+    }
+
+    pub fn sight_range(self) -> f32 {
+        match self {
+            Difficulty::Easy => 40.0,
+            Difficulty::Normal => 60.0,
+            Difficulty::Hard => 80.0,
+        }
+    }
+
+    // whether the bot aims where a moving target is going rather than where it is
+    fn leads_target(self) -> bool {
+        matches!(self, Difficulty::Hard)
+    }
+}
+
+/// What a bot remembers between ticks. It has a generator of its own, seeded when the bot is
+/// added, so that its choices do not shift the spawn points and other draws of the match.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BotState {
+    difficulty: Difficulty,
+    rng: ChaCha8Rng,
+    // radians from the x axis
+    heading: f32,
+    ticks_until_turn: u32,
+    // waypoints still to walk through on the way to where the bot is wandering to
+    route: Vec<(f32, f32)>,
+    strafe_left: bool,
+    ticks_until_shot: u32,
+    shots_fired: u64,
+}
+
+/// What a bot can see this tick, gathered from the physics world by the game.
+pub struct Perception {
+    pub eye: Vector3<f32>,
+    pub feet: Vector3<f32>,
+    pub can_shoot: bool,
+    pub obstacle_ahead: Option<Obstacle>,
+    pub target: Option<Target>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Obstacle {
+    Low,
+    High,
+}
+
+/// The nearest enemy in sight.
+pub struct Target {
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+}
+
+pub fn init(difficulty: Difficulty, seed: u64) -> BotState {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    BotState {
+        difficulty,
+        heading: rng.gen_range(0.0..std::f32::consts::TAU),
+        ticks_until_turn: rng.gen_range(MIN_WANDER_TICKS..MAX_WANDER_TICKS),
+        route: Vec::new(),
+        strafe_left: rng.gen(),
+        ticks_until_shot: difficulty.ticks_between_shots(),
+        shots_fired: 0,
+        rng,
+    }
+}
+
+pub fn create_body(id: String, team_id: String, translation: (f32, f32, f32)) -> body::Body {
+    body::create_player(&id, &team_id, translation, BOT_HP)
+}
+
+pub fn get_difficulty(state: &BotState) -> Difficulty {
+    state.difficulty
+}
+
+/// The horizontal direction the bot means to wander in.
+pub fn get_heading(state: &BotState) -> Vector2<f32> {
+    Vector2::new(state.heading.cos(), state.heading.sin())
+}
+
+pub fn hash(hasher: &mut Fnv1a, state: &BotState) {
+    hasher.write(&state.rng.get_word_pos().to_le_bytes());
+    hasher.write(&state.shots_fired.to_le_bytes());
+    // a fixed width, so that 32 and 64 bit builds hash the same state alike
+    hasher.write(&(state.route.len() as u64).to_le_bytes());
+}
+
+/// Decides on the bot's inputs for this tick: fighting the target in sight if there is one,
+/// wandering around whatever is in its way otherwise. Wandering bots find their way with
+/// `find_path`, which gives the waypoints from one point of the arena to another.
+pub fn think(
+    state: &mut BotState,
+    id: &str,
+    team_id: Option<&str>,
+    perception: &Perception,
+    find_path: impl Fn((f32, f32), (f32, f32)) -> Option<Vec<(f32, f32)>>,
+) -> Vec<UserInput> {
+    state.ticks_until_shot = state.ticks_until_shot.saturating_sub(1);
+    match &perception.target {
+        Some(target) => fight(state, id, team_id, perception, target),
+        None => wander(state, id, perception, find_path),
+    }
+}
+
+fn wander(
+    state: &mut BotState,
+    id: &str,
+    perception: &Perception,
+    find_path: impl Fn((f32, f32), (f32, f32)) -> Option<Vec<(f32, f32)>>,
+) -> Vec<UserInput> {
+    let mut inputs = Vec::new();
+    let feet = perception.feet;
+
+    state.ticks_until_turn = state.ticks_until_turn.saturating_sub(1);
+    if feet.x.abs() > WANDER_LIMIT || feet.y.abs() > WANDER_LIMIT {
+        state.heading = (-feet.y).atan2(-feet.x);
+        state.route.clear();
+    } else if perception.obstacle_ahead == Some(Obstacle::High) {
+        // something the route does not know about, such as a crate, is in the way
+        state.heading += state.rng.gen_range(0.5..1.5) * std::f32::consts::PI;
+        state.ticks_until_turn = state.rng.gen_range(MIN_WANDER_TICKS..MAX_WANDER_TICKS);
+        state.route.clear();
+    } else if state.ticks_until_turn == 0 {
+        let destination = (
+            state.rng.gen_range(-WANDER_LIMIT..WANDER_LIMIT),
+            state.rng.gen_range(-WANDER_LIMIT..WANDER_LIMIT),
+        );
+        state.route = find_path((feet.x, feet.y), destination).unwrap_or_default();
+        state.ticks_until_turn = if state.route.is_empty() {
+            state.heading = state.rng.gen_range(0.0..std::f32::consts::TAU);
+            state.rng.gen_range(MIN_WANDER_TICKS..MAX_WANDER_TICKS)
+        } else {
+            MAX_ROUTE_TICKS
+        };
+    }
+
+    while let Some((x, y)) = state.route.first().copied() {
+        let offset = Vector2::new(x - feet.x, y - feet.y);
+        if offset.norm() > WAYPOINT_RADIUS {
+            state.heading = offset.y.atan2(offset.x);
+            break;
+        }
+        state.route.remove(0);
+        if state.route.is_empty() {
+            state.ticks_until_turn = state.rng.gen_range(MIN_WANDER_TICKS..MAX_WANDER_TICKS);
+        }
+    }
+
+    if perception.obstacle_ahead == Some(Obstacle::Low) {
+        inputs.push(UserInput::Jump { id: id.to_string() });
+    }
+
+    let heading = get_heading(state);
+    inputs.push(UserInput::Aim {
+        id: id.to_string(),
+        yaw: get_yaw(heading),
+        pitch: 0.0,
+    });
+    inputs.push(UserInput::Move {
+        id: id.to_string(),
+        direction: (heading.x, heading.y),
+    });
+    inputs
+}
+
+fn fight(
+    state: &mut BotState,
+    id: &str,
+    team_id: Option<&str>,
+    perception: &Perception,
+    target: &Target,
+) -> Vec<UserInput> {
+    let mut inputs = Vec::new();
+    let difficulty = state.difficulty;
+
+    let offset = target.position - perception.eye;
+    let aim_point = if difficulty.leads_target() {
+        target.position + target.velocity * (offset.norm() / BULLET_SPEED)
+    } else {
+        target.position
+    };
+    let aim_direction = aim_point - perception.eye;
+    let error = difficulty.aim_error_degrees();
+    let yaw = get_yaw(aim_direction.xy()) + state.rng.gen_range(-error..=error);
+    let horizontal_distance = aim_direction.xy().norm();
+    let pitch = aim_direction.z.atan2(horizontal_distance).to_degrees()
+        + state.rng.gen_range(-error..=error);
+    inputs.push(UserInput::Aim {
+        id: id.to_string(),
+        yaw,
+        pitch,
+    });
+
+    let towards = offset.xy().try_normalize(f32::EPSILON).unwrap_or_default();
+    let direction = if offset.xy().norm() > ENGAGE_DISTANCE {
+        towards
+    } else {
+        if state.rng.gen_bool(1.0 / MIN_WANDER_TICKS as f64) {
+            state.strafe_left = !state.strafe_left;
+        }
+        let left = Vector2::new(-towards.y, towards.x);
+        if state.strafe_left {
+            left
+        } else {
+            -left
+        }
+    };
+    inputs.push(UserInput::Move {
+        id: id.to_string(),
+        direction: (direction.x, direction.y),
+    });
+
+    if perception.can_shoot && state.ticks_until_shot == 0 {
+        let bullet_direction = get_aim_direction(yaw, pitch);
+        let muzzle = perception.eye + bullet_direction * MUZZLE_DISTANCE;
+        let linvel = bullet_direction * BULLET_SPEED;
+        let bullet = body::create_bullet(
+            &format!("{}-bullet-{}", id, state.shots_fired),
+            id,
+            team_id,
+            (muzzle.x, muzzle.y, muzzle.z),
+            (linvel.x, linvel.y, linvel.z),
+        );
+        inputs.push(UserInput::Shoot { body: bullet });
+        state.shots_fired += 1;
+        state.ticks_until_shot = difficulty.ticks_between_shots();
+    }
+
+    inputs
+}
+
+/// The yaw, in degrees, that turns a body to face along `direction`. Bodies face along the y axis
+/// when unrotated.
+fn get_yaw(direction: Vector2<f32>) -> f32 {
+    (-direction.x).atan2(direction.y).to_degrees()
+}
+
+fn get_aim_direction(yaw: f32, pitch: f32) -> Vector3<f32> {
+    let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+    Vector3::new(
+        -yaw.sin() * pitch.cos(),
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perception(target: Option<Target>, obstacle_ahead: Option<Obstacle>) -> Perception {
+        Perception {
+            eye: Vector3::new(0.0, 0.0, 1.6),
+            feet: Vector3::new(0.0, 0.0, 0.0),
+            can_shoot: true,
+            obstacle_ahead,
+            target,
+        }
+    }
+
+    fn no_path(_from: (f32, f32), _to: (f32, f32)) -> Option<Vec<(f32, f32)>> {
+        None
+    }
+
+    #[test]
+    fn a_bot_faces_and_walks_the_way_it_wanders() {
+        let mut state = init(Difficulty::Normal, 1);
+        let inputs = think(
+            &mut state,
+            "bot-1",
+            Some("red"),
+            &perception(None, None),
+            no_path,
+        );
+
+        let heading = get_heading(&state);
+        let facing = get_aim_direction(get_yaw(heading), 0.0);
+        assert!((facing.xy() - heading).norm() < 1e-4);
+        assert!(inputs.iter().any(|input| matches!(
+            input,
+            UserInput::Move { direction, .. } if (direction.0, direction.1) == (heading.x, heading.y)
+        )));
+    }
+
+    #[test]
+    fn a_wandering_bot_follows_its_route() {
+        let mut state = init(Difficulty::Normal, 1);
+        state.ticks_until_turn = 1;
+        let route = |_from, _to| Some(vec![(10.0, 0.0), (10.0, 10.0)]);
+
+        think(&mut state, "bot-1", None, &perception(None, None), route);
+        assert!((get_heading(&state) - Vector2::new(1.0, 0.0)).norm() < 1e-4);
+
+        let mut arrived = perception(None, None);
+        arrived.feet = Vector3::new(10.0, 0.2, 0.0);
+        think(&mut state, "bot-1", None, &arrived, route);
+        assert!((get_heading(&state) - Vector2::new(0.0, 1.0)).norm() < 1e-4);
+        assert_eq!(state.route, vec![(10.0, 10.0)]);
+    }
+
+    #[test]
+    fn a_bot_jumps_low_obstacles_and_turns_away_from_high_ones() {
+        let mut state = init(Difficulty::Normal, 1);
+        let heading = state.heading;
+        let inputs = think(
+            &mut state,
+            "bot-1",
+            None,
+            &perception(None, Some(Obstacle::Low)),
+            no_path,
+        );
+        assert!(inputs
+            .iter()
+            .any(|input| matches!(input, UserInput::Jump { .. })));
+        assert_eq!(state.heading, heading);
+
+        let inputs = think(
+            &mut state,
+            "bot-1",
+            None,
+            &perception(None, Some(Obstacle::High)),
+            no_path,
+        );
+        assert!(!inputs
+            .iter()
+            .any(|input| matches!(input, UserInput::Jump { .. })));
+        assert_ne!(state.heading, heading);
+    }
+
+    #[test]
+    fn a_bot_shoots_at_its_target_once_it_is_ready() {
+        let mut state = init(Difficulty::Hard, 1);
+        let target = || Target {
+            position: Vector3::new(20.0, 0.0, 1.6),
+            velocity: Vector3::zeros(),
+        };
+        let get_shot_velocities = |inputs: &[UserInput]| {
+            inputs
+                .iter()
+                .filter_map(|input| match input {
+                    UserInput::Shoot { body } => Some(body.linvel),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut shots = Vec::new();
+        for _ in 0..Difficulty::Hard.ticks_between_shots() * 2 {
+            let inputs = think(
+                &mut state,
+                "bot-1",
+                Some("red"),
+                &perception(Some(target()), None),
+                no_path,
+            );
+            shots.extend(get_shot_velocities(&inputs));
+        }
+
+        assert_eq!(shots.len(), 2);
+        for (x, y, _z) in shots {
+            // aimed along the x axis, give or take the difficulty's aim error
+            assert!(x > 0.0 && (y / x).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::config::Config;
+use crate::game;
+use crate::mode::{self, GameMode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Deserialize, Serialize)]
+struct Checkpoint {
+    game: Vec<u8>,
+    mode: Vec<u8>,
+}
+
+/// Writes the game and its mode to `path`, for a new process to resume with `--restore`.
+pub fn save(path: &str, game_state: &game::Game, game_mode: &dyn GameMode) -> Result<(), String> {
+    let checkpoint = Checkpoint {
+        game: game::snapshot(game_state)?,
+        mode: game_mode.snapshot()?,
+    };
+    let bytes = rmp_serde::to_vec(&checkpoint).map_err(|err| err.to_string())?;
+
+    // written alongside and then moved into place, so a crash mid-write never leaves a torn
+    // checkpoint where the previous good one was
+    let partial_path = format!("{}.partial", path);
+    fs::write(&partial_path, bytes).map_err(|err| err.to_string())?;
+    fs::rename(&partial_path, path).map_err(|err| err.to_string())
+}
+
+/// Reads a checkpoint written by `save`. The mode is rebuilt from the config before its state is
+/// restored, so the process must be started with the same mode as the one that saved it.
+pub fn load(path: &str, config: &Config) -> Result<(game::Game, Box<dyn GameMode>), String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    let checkpoint: Checkpoint = rmp_serde::from_slice(&bytes).map_err(|err| err.to_string())?;
+
+    let game_state = game::restore(&checkpoint.game)?;
+    let mut game_mode = mode::build(&config.mode);
+    game_mode.restore(&checkpoint.mode)?;
+    Ok((game_state, game_mode))
+}
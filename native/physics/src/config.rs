@@ -0,0 +1,54 @@
+use crate::mode::ModeConfig;
+use crate::movement::MovementConfig;
+use crate::rules::GameRules;
+use crate::terrain::FloorConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub rules: GameRules,
+    pub mode: ModeConfig,
+    pub movement: MovementConfig,
+    pub floor: FloorConfig,
+    // the share of generated crates that bullets can wear down and remove, from 0 to 1
+    pub destructible_obstacle_ratio: f64,
+    // when set, a state_hash event is written every this many ticks so that clients and replays
+    // can spot a desync
+    pub state_hash_interval_ticks: Option<u64>,
+    // when set, tick timings and body counts are written to stderr as a metrics line every this
+    // many ticks
+    pub metrics_interval_ticks: Option<u64>,
+}
+
+/// Returns the value following a command line flag, if the flag was given.
+pub fn get_arg(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == flag).map(|index| {
+        args.get(index + 1)
+            .unwrap_or_else(|| panic!("Missing value after {}", flag))
+            .clone()
+    })
+}
+
+pub fn from_args() -> Config {
+    match get_arg("--config") {
+        Some(path) => load(&path).expect("Failed to load config"),
+        None => Config::default(),
+    }
+}
+
+/// The seed for the arena and everything random in the match, taken from `--seed` or picked at
+/// random.
+pub fn seed_from_args() -> u64 {
+    match get_arg("--seed") {
+        Some(seed) => seed.parse().expect("Invalid --seed"),
+        None => rand::random(),
+    }
+}
+
+pub fn load(path: &str) -> Result<Config, String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    serde_json::from_str(&contents).map_err(|err| err.to_string())
+}
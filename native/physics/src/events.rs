@@ -0,0 +1,62 @@
+use crate::pickup::PickupEffect;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    Hit {
+        id: String,
+        team_id: Option<String>,
+        attacker_id: Option<String>,
+        attacker_team_id: Option<String>,
+        hp: i32,
+    },
+    Eliminated {
+        id: String,
+        team_id: Option<String>,
+        attacker_id: Option<String>,
+        attacker_team_id: Option<String>,
+    },
+    RespawnCountdown {
+        id: String,
+        seconds_remaining: u64,
+    },
+    Respawned {
+        id: String,
+        team_id: Option<String>,
+        translation: (f32, f32, f32),
+    },
+    PickupCollected {
+        id: String,
+        player_id: String,
+        #[serde(flatten)]
+        effect: PickupEffect,
+    },
+    PickupRespawned {
+        id: String,
+    },
+    // a body left the world for good, e.g. a destructible obstacle that was shot to pieces
+    BodyRemoved {
+        id: String,
+        removed_by: Option<String>,
+    },
+    // a checksum of the world after this tick, see `game::state_hash`
+    StateHash {
+        tick: u64,
+        hash: u64,
+    },
+    // a checkpoint asked for with the snapshot action is complete and can be restored from
+    SnapshotSaved {
+        path: String,
+    },
+    // the answer to a find_path action, without waypoints when there is no way through
+    Path {
+        request_id: String,
+        waypoints: Option<Vec<(f32, f32, f32)>>,
+    },
+    MatchEnded {
+        winning_team_id: Option<String>,
+        scores: BTreeMap<String, i32>,
+    },
+}
@@ -1,26 +1,59 @@
-use rapier3d::math::Rotation;
-use rapier3d::na::UnitQuaternion;
-use rapier3d::na::Vector3;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rapier3d::control::KinematicCharacterController;
+use rapier3d::na::{Vector2, Vector3};
 use rapier3d::prelude::ColliderHandle;
 use rapier3d::prelude::CollisionEvent;
 use rapier3d::prelude::RigidBody;
 use rapier3d::prelude::RigidBodyHandle;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::f32::consts::PI;
+use std::time::Instant;
 
 use crate::body;
+use crate::bot;
+use crate::config;
+use crate::events::GameEvent;
 use crate::init;
+use crate::metrics::StepTimings;
+use crate::movement;
+use crate::nav;
 use crate::physics_world;
-use crate::util::to_vec3;
+use crate::pickup;
+use crate::rotation::EulerDegrees;
+use crate::rules;
+use crate::shape::ShapeDescriptor;
+use crate::user_input::UserInput;
+use crate::util::{to_vec3, Fnv1a};
 
+#[derive(Serialize, Deserialize)]
 pub struct Game {
     world: physics_world::PhysicsWorld,
     metadata_by_handle: HashMap<RigidBodyHandle, BodyMetadata>,
     handle_by_body_id: HashMap<String, RigidBodyHandle>,
+    rules: rules::GameRules,
+    movement: movement::MovementConfig,
+    character_controller: KinematicCharacterController,
+    tick: u64,
+    // everything random after the arena is built, such as spawn points, draws from this so that a
+    // seed and the same inputs always play out the same way
+    rng: ChaCha8Rng,
+    state_hash_interval_ticks: Option<u64>,
+    nav: nav::NavGrid,
+    // drained at the end of every tick, so a snapshot taken between ticks has none to keep
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+    #[serde(skip)]
+    snapshot_requests: Vec<String>,
+    // how long the parts of the last step took, for the port loop's metrics
+    #[serde(skip)]
+    step_timings: StepTimings,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct BodyMetadata {
     id: String,
     team_id: Option<String>,
@@ -28,42 +61,359 @@ struct BodyMetadata {
     class: body::BodyClass,
     dimensions: (f32, f32, f32),
     hp: i32,
+    max_hp: i32,
+    spawned_at_tick: u64,
+    respawn_at_tick: Option<u64>,
+    // fractional damage not yet deducted from hp, e.g. from reduced friendly fire
+    pending_damage: f32,
+    pickup: Option<pickup::Pickup>,
+    buffs: Vec<pickup::ActiveBuff>,
+    ammo: Option<i32>,
+    character: Option<movement::CharacterState>,
+    aim: Option<movement::Aim>,
+    shape: Option<ShapeDescriptor>,
+    destructible: bool,
+    // set for players driven by the built-in bot rather than a client
+    bot: Option<bot::BotState>,
 }
 
-pub fn init() -> Game {
+pub fn init(config: &config::Config, seed: u64) -> Game {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let initial_bodies: Vec<body::Body> =
+        init::get_initial_bodies(&config.floor, config.destructible_obstacle_ratio, &mut rng);
+    build(config, rng, &initial_bodies)
+}
+
+/// Builds an arena with nothing but the floor in it, for setting a scene up by hand.
+#[cfg(test)]
+pub fn init_empty(config: &config::Config, seed: u64) -> Game {
+    let floor = init::create_floor(&config.floor);
+    build(config, ChaCha8Rng::seed_from_u64(seed), &[floor])
+}
+
+fn build(config: &config::Config, rng: ChaCha8Rng, initial_bodies: &[body::Body]) -> Game {
     let mut game_state = Game {
         world: physics_world::init(),
         metadata_by_handle: HashMap::new(),
         handle_by_body_id: HashMap::new(),
+        rules: config.rules.clone(),
+        movement: config.movement.clone(),
+        character_controller: movement::build_controller(&config.movement),
+        tick: 0,
+        rng,
+        state_hash_interval_ticks: config.state_hash_interval_ticks,
+        nav: nav::NavGrid::default(),
+        events: Vec::new(),
+        snapshot_requests: Vec::new(),
+        step_timings: StepTimings::default(),
     };
 
-    let initial_bodies: Vec<body::Body> = init::get_initial_bodies();
     // add initial bodies to world and metadata store
-    for body in &initial_bodies {
+    for body in initial_bodies {
         upsert_body(&mut game_state, body);
     }
+    game_state.nav = build_nav_grid(&game_state);
 
     game_state
 }
 
+// a cell is as wide as a player, so a route through free cells leaves room to walk
+const NAV_CELL_SIZE: f32 = 1.0;
+// how much headroom a player needs above whatever it can step onto
+const NAV_HEADROOM: f32 = 1.6;
+
+/// Marks where a standing player fits among the arena's fixed obstacles. Obstacles added or
+/// destroyed later in the match are not reflected, and neither are props, which players can push
+/// aside.
+fn build_nav_grid(game_state: &Game) -> nav::NavGrid {
+    let world = &game_state.world;
+    let floor = game_state.handle_by_body_id.get(init::FLOOR_ID);
+    let obstacles = physics_world::get_fixed_colliders(world, floor);
+    let step_height = game_state.movement.step_height;
+
+    nav::build(
+        init::ARENA_WIDTH,
+        NAV_CELL_SIZE,
+        &obstacles,
+        |collider| physics_world::get_footprint(world, collider, body::PLAYER_COLLIDER_RADIUS),
+        |collider, (x, y)| {
+            let base = Vector3::new(x, y, get_floor_height(game_state, x, y) + step_height);
+            physics_world::is_column_blocked(
+                world,
+                collider,
+                base,
+                body::PLAYER_COLLIDER_RADIUS,
+                NAV_HEADROOM,
+            )
+        },
+    )
+}
+
+/// Finds a walkable route between two points, see `nav::find_path`. The waypoints are placed on
+/// the floor.
+pub fn find_path(
+    game_state: &Game,
+    from: (f32, f32, f32),
+    to: (f32, f32, f32),
+) -> Option<Vec<(f32, f32, f32)>> {
+    let waypoints = nav::find_path(&game_state.nav, (from.0, from.1), (to.0, to.1))?;
+    Some(
+        waypoints
+            .into_iter()
+            .map(|(x, y)| (x, y, get_floor_height(game_state, x, y)))
+            .collect(),
+    )
+}
+
+pub fn report_path(
+    game_state: &mut Game,
+    request_id: String,
+    from: (f32, f32, f32),
+    to: (f32, f32, f32),
+) {
+    let waypoints = find_path(game_state, from, to);
+    game_state.events.push(GameEvent::Path {
+        request_id,
+        waypoints,
+    });
+}
+
 pub fn step(game_state: &mut Game) -> HashSet<RigidBodyHandle> {
+    game_state.tick += 1;
+    expire_buffs(game_state);
+    let respawned_handles = update_respawns(game_state);
+    let characters_start = Instant::now();
+    update_characters(game_state);
+    game_state.step_timings.characters = characters_start.elapsed();
+
+    let physics_start = Instant::now();
     let collisions = physics_world::step(&mut game_state.world);
+    game_state.step_timings.physics = physics_start.elapsed();
 
+    let collisions_start = Instant::now();
     for collision_event in collisions {
         handle_contact(collision_event, game_state);
     }
+    game_state.step_timings.collisions = collisions_start.elapsed();
 
+    let stale_sweep_start = Instant::now();
     let active_handles = physics_world::get_active_handles(&game_state.world);
     let _any_removed = remove_stale_objects(game_state, active_handles);
+    game_state.step_timings.stale_sweep = stale_sweep_start.elapsed();
+
+    let is_hash_tick = game_state
+        .state_hash_interval_ticks
+        .is_some_and(|interval| game_state.tick % interval.max(1) == 0);
+    if is_hash_tick {
+        game_state.events.push(GameEvent::StateHash {
+            tick: game_state.tick,
+            hash: state_hash(game_state),
+        });
+    }
+
+    let mut updated_handles = physics_world::get_active_handles(&game_state.world);
+    updated_handles.extend(respawned_handles);
+    updated_handles
+}
+
+fn update_characters(game_state: &mut Game) {
+    let dt = physics_world::get_dt(&game_state.world);
+    let gravity = physics_world::get_gravity(&game_state.world);
+
+    // a stable order keeps the simulation reproducible regardless of hash map iteration
+    let mut handles: Vec<RigidBodyHandle> = game_state
+        .metadata_by_handle
+        .iter()
+        .filter(|(_handle, metadata)| metadata.character.is_some())
+        .map(|(handle, _metadata)| *handle)
+        .collect();
+    handles.sort_by_key(|handle| handle.into_raw_parts());
+
+    for handle in handles {
+        let metadata = match game_state.metadata_by_handle.get_mut(&handle) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        let speed_multiplier = pickup::get_speed_multiplier(&metadata.buffs);
+        let character = match metadata.character.as_mut() {
+            Some(character) => character,
+            None => continue,
+        };
+
+        if let Some(height) = movement::get_stance_change(&game_state.movement, character) {
+            let resized = physics_world::resize_player(
+                &mut game_state.world,
+                &handle,
+                metadata.dimensions.2,
+                height,
+            );
+            if resized {
+                character.crouching = character.wants_crouch;
+                metadata.dimensions.2 = height;
+            }
+        }
+
+        let facing = physics_world::get_facing(&game_state.world, &handle);
+        let velocity = movement::step_velocity(
+            &game_state.movement,
+            character,
+            facing,
+            speed_multiplier,
+            gravity,
+            dt,
+        );
+        let desired_translation = velocity * dt;
+        if let Some(effective_movement) = physics_world::move_character(
+            &mut game_state.world,
+            &game_state.character_controller,
+            &handle,
+            desired_translation,
+        ) {
+            movement::apply_movement(character, desired_translation, &effective_movement, dt);
+        }
+    }
+}
+
+const MAX_SPAWN_ATTEMPTS: usize = 50;
+// keeps spawning capsules clear of sloped terrain; gravity settles them onto it
+const SPAWN_CLEARANCE: f32 = 0.1;
+
+fn update_respawns(game_state: &mut Game) -> HashSet<RigidBodyHandle> {
+    let ticks_per_second = (1000.0 / get_tick_ms(game_state)).round() as u64;
+    let mut pending_respawns: Vec<(RigidBodyHandle, u64)> = game_state
+        .metadata_by_handle
+        .iter()
+        .filter_map(|(handle, metadata)| metadata.respawn_at_tick.map(|tick| (*handle, tick)))
+        .collect();
+    // spawn points are drawn in turn from the shared generator, so the order has to be stable
+    pending_respawns.sort_by_key(|(handle, _respawn_at_tick)| handle.into_raw_parts());
+
+    let mut respawned_handles = HashSet::new();
+    for (handle, respawn_at_tick) in pending_respawns {
+        let remaining_ticks = respawn_at_tick.saturating_sub(game_state.tick);
+        let is_pickup = matches!(
+            game_state.metadata_by_handle[&handle].class,
+            body::BodyClass::Pickup
+        );
+        if remaining_ticks == 0 {
+            let respawned = if is_pickup {
+                respawn_pickup(game_state, handle)
+            } else {
+                respawn_player(game_state, handle)
+            };
+            if respawned {
+                respawned_handles.insert(handle);
+            }
+        } else if !is_pickup && remaining_ticks % ticks_per_second == 0 {
+            let id = game_state.metadata_by_handle[&handle].id.clone();
+            game_state.events.push(GameEvent::RespawnCountdown {
+                id,
+                seconds_remaining: remaining_ticks / ticks_per_second,
+            });
+        }
+    }
+
+    respawned_handles
+}
+
+/// Teleports a dead player to a free spawn point with full health. If no free spot is found the
+/// respawn is retried on the next tick.
+fn respawn_player(game_state: &mut Game, handle: RigidBodyHandle) -> bool {
+    let height = match game_state.metadata_by_handle.get(&handle) {
+        Some(metadata) => match &metadata.character {
+            Some(character) => character.standing_height,
+            None => metadata.dimensions.2,
+        },
+        None => return false,
+    };
+    // a player who died crouching gets back up before looking for room to spawn
+    physics_world::set_collider_shape(
+        &mut game_state.world,
+        &handle,
+        body::get_player_shape(height),
+    );
+
+    let translation = match find_spawn_point(game_state, &handle, height) {
+        Some(translation) => translation,
+        None => return false,
+    };
+
+    physics_world::teleport_body(&mut game_state.world, &handle, translation);
+    physics_world::set_collision_groups(
+        &mut game_state.world,
+        &handle,
+        body::get_collision_groups(body::BodyClass::Player),
+    );
+    if let Some(metadata) = game_state.metadata_by_handle.get_mut(&handle) {
+        metadata.hp = metadata.max_hp;
+        metadata.pending_damage = 0.0;
+        metadata.spawned_at_tick = game_state.tick;
+        metadata.respawn_at_tick = None;
+        metadata.dimensions.2 = height;
+        metadata.character = Some(movement::init_character(
+            &game_state.movement,
+            (0.0, 0.0, 0.0),
+            height,
+        ));
+
+        game_state.events.push(GameEvent::Respawned {
+            id: metadata.id.clone(),
+            team_id: metadata.team_id.clone(),
+            translation: (translation.x, translation.y, translation.z),
+        });
+    }
+
+    true
+}
 
-    physics_world::get_active_handles(&game_state.world)
+/// Looks for a point on the spawn ring where the body fits, giving up after a few attempts.
+fn find_spawn_point(
+    game_state: &mut Game,
+    handle: &RigidBodyHandle,
+    height: f32,
+) -> Option<Vector3<f32>> {
+    for _ in 0..MAX_SPAWN_ATTEMPTS {
+        let (x, y, z) = init::get_spawn_candidate(&mut game_state.rng, height);
+        let translation = Vector3::new(
+            x,
+            y,
+            z + get_floor_height(game_state, x, y) + SPAWN_CLEARANCE,
+        );
+        if physics_world::is_space_free(&game_state.world, handle, translation) {
+            return Some(translation);
+        }
+    }
+    None
+}
+
+fn respawn_pickup(game_state: &mut Game, handle: RigidBodyHandle) -> bool {
+    physics_world::set_colliders_enabled(&mut game_state.world, &handle, true);
+    if let Some(metadata) = game_state.metadata_by_handle.get_mut(&handle) {
+        metadata.respawn_at_tick = None;
+        game_state.events.push(GameEvent::PickupRespawned {
+            id: metadata.id.clone(),
+        });
+    }
+
+    true
+}
+
+fn expire_buffs(game_state: &mut Game) {
+    let tick = game_state.tick;
+    for metadata in game_state.metadata_by_handle.values_mut() {
+        metadata.buffs.retain(|buff| buff.expires_at_tick > tick);
+    }
 }
 
 fn remove_stale_objects(game_state: &mut Game, handles: HashSet<RigidBodyHandle>) -> bool {
+    // removal order decides which arena slots later bodies reuse, so it is kept stable too
+    let mut handles: Vec<RigidBodyHandle> = handles.into_iter().collect();
+    handles.sort_by_key(|handle| handle.into_raw_parts());
+
     let mut any_removed = false;
     for handle in handles {
         if let Some(body) = get_body_from_handle(game_state, &handle) {
-            if is_stale(&body) {
+            if is_stale(game_state, &body) {
                 delete_body(body, game_state);
                 any_removed = true;
             }
@@ -73,9 +423,9 @@ fn remove_stale_objects(game_state: &mut Game, handles: HashSet<RigidBodyHandle>
     any_removed
 }
 
-fn is_stale(body: &body::Body) -> bool {
+fn is_stale(game_state: &Game, body: &body::Body) -> bool {
     match body.class {
-        body::BodyClass::Bullet => is_on_floor(body) || is_at_rest(body),
+        body::BodyClass::Bullet => is_on_floor(game_state, body) || is_at_rest(body),
         _ => false,
     }
 }
@@ -84,12 +434,21 @@ fn is_at_rest(body::Body { linvel, .. }: &body::Body) -> bool {
     linvel.0.round() == 0.0 && linvel.1.round() == 0.0 && linvel.2.round() == 0.0
 }
 
-fn is_on_floor(body: &body::Body) -> bool {
-    let translation = body.translation;
-    let origin_height = translation.2;
+fn is_on_floor(game_state: &Game, body: &body::Body) -> bool {
+    let (x, y, origin_height) = body.translation;
     let object_height = body.dimensions.2;
 
-    origin_height <= (object_height / 2.0)
+    origin_height - (object_height / 2.0) <= get_floor_height(game_state, x, y)
+}
+
+/// Returns the height of the floor's surface at a point. Outside the floor this is the height of a
+/// flat floor, so bodies falling past the edge are still treated as landed.
+fn get_floor_height(game_state: &Game, x: f32, y: f32) -> f32 {
+    game_state
+        .handle_by_body_id
+        .get(init::FLOOR_ID)
+        .and_then(|handle| physics_world::get_surface_height(&game_state.world, handle, x, y))
+        .unwrap_or(0.0)
 }
 
 pub fn get_handles(game_state: &Game) -> HashSet<RigidBodyHandle> {
@@ -99,30 +458,227 @@ pub fn get_handles(game_state: &Game) -> HashSet<RigidBodyHandle> {
         .collect()
 }
 
+pub fn get_tick(game_state: &Game) -> u64 {
+    game_state.tick
+}
+
+pub fn get_step_timings(game_state: &Game) -> StepTimings {
+    game_state.step_timings
+}
+
+/// Counts the bodies in the world by class, including pickups waiting to respawn.
+pub fn count_bodies_by_class(game_state: &Game) -> BTreeMap<body::BodyClass, usize> {
+    let mut counts = BTreeMap::new();
+    for metadata in game_state.metadata_by_handle.values() {
+        *counts.entry(metadata.class).or_insert(0) += 1;
+    }
+    counts
+}
+
 pub fn get_tick_ms(game_state: &Game) -> f32 {
     physics_world::get_dt(&game_state.world) * 1000.0
 }
 
+pub fn get_elapsed_ms(game_state: &Game) -> f32 {
+    game_state.tick as f32 * get_tick_ms(game_state)
+}
+
+/// Knocks back the props (and anything else dynamic) caught in a blast, returning the bodies
+/// that were pushed.
+pub fn explode(
+    game_state: &mut Game,
+    center: (f32, f32, f32),
+    radius: f32,
+    impulse: f32,
+) -> Vec<RigidBodyHandle> {
+    physics_world::apply_explosion(&mut game_state.world, to_vec3(center), radius, impulse)
+}
+
+/// Drains the events produced since the last call.
+pub fn take_events(game_state: &mut Game) -> Vec<GameEvent> {
+    std::mem::take(&mut game_state.events)
+}
+
+const HASHED_POSITION_STEP: f32 = 1e-4;
+const HASHED_ROTATION_STEP: f32 = 1e-5;
+const HASHED_VELOCITY_STEP: f32 = 1e-3;
+const HASHED_STAMINA_STEP: f32 = 1e-3;
+
+/// A checksum of the simulation, for telling whether two runs of the same match are still in step.
+/// Bodies are visited by id, so neither hash map order nor handle numbering affects it, and floats
+/// are quantised so that only differences that matter to the game change it.
+pub fn state_hash(game_state: &Game) -> u64 {
+    let mut bodies: Vec<(&String, &RigidBodyHandle)> =
+        game_state.handle_by_body_id.iter().collect();
+    bodies.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut hasher = Fnv1a::default();
+    hasher.write(&game_state.tick.to_le_bytes());
+    hasher.write(&game_state.rng.get_word_pos().to_le_bytes());
+    for (id, handle) in bodies {
+        hasher.write(id.as_bytes());
+        hasher.write(&[0]);
+        if let Some(rigid_body) = physics_world::get_body(&game_state.world, handle) {
+            hash_rigid_body(&mut hasher, rigid_body);
+        }
+        if let Some(metadata) = game_state.metadata_by_handle.get(handle) {
+            hash_metadata(&mut hasher, metadata);
+        }
+    }
+    hasher.finish()
+}
+
+fn hash_rigid_body(hasher: &mut Fnv1a, rigid_body: &RigidBody) {
+    let position = rigid_body.position();
+    for value in position.translation.vector.iter() {
+        hasher.write_quantised(*value, HASHED_POSITION_STEP);
+    }
+    for value in position.rotation.coords.iter() {
+        hasher.write_quantised(*value, HASHED_ROTATION_STEP);
+    }
+    for value in rigid_body.linvel().iter().chain(rigid_body.angvel().iter()) {
+        hasher.write_quantised(*value, HASHED_VELOCITY_STEP);
+    }
+}
+
+fn hash_metadata(hasher: &mut Fnv1a, metadata: &BodyMetadata) {
+    hasher.write(&metadata.hp.to_le_bytes());
+    hasher.write(&metadata.ammo.unwrap_or(-1).to_le_bytes());
+    hasher.write(&metadata.respawn_at_tick.unwrap_or(0).to_le_bytes());
+    for buff in &metadata.buffs {
+        hasher.write(&buff.expires_at_tick.to_le_bytes());
+    }
+    if let Some(character) = &metadata.character {
+        hasher.write(&[
+            character.grounded as u8,
+            character.sprinting as u8,
+            character.crouching as u8,
+        ]);
+        hasher.write(&character.dash_cooldown_ticks.to_le_bytes());
+        hasher.write_quantised(character.stamina, HASHED_STAMINA_STEP);
+    }
+    if let Some(bot) = &metadata.bot {
+        bot::hash(hasher, bot);
+    }
+}
+
+// bumped whenever the serialised layout of `Game` changes, so that old snapshots are rejected
+// rather than misread. `the_snapshot_layout_matches_its_version` checks the layout against
+// tests/snapshot_layout.txt, which starts with the version it was written for.
+const SNAPSHOT_VERSION: u32 = 3;
+
+/// Serialises the complete state of the game, physics world included, so that it can be resumed
+/// with `restore` in another process. Structs are written with their field names because the
+/// flattened and tagged layouts shared with the JSON protocol cannot be read back without them.
+pub fn snapshot(game_state: &Game) -> Result<Vec<u8>, String> {
+    rmp_serde::to_vec_named(&(SNAPSHOT_VERSION, game_state)).map_err(|err| err.to_string())
+}
+
+pub fn restore(bytes: &[u8]) -> Result<Game, String> {
+    let (version, _game_state): (u32, IgnoredAny) =
+        rmp_serde::from_slice(bytes).map_err(|err| err.to_string())?;
+    if version != SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported snapshot version {} (expected {})",
+            version, SNAPSHOT_VERSION
+        ));
+    }
+
+    let (_version, game_state): (u32, Game) =
+        rmp_serde::from_slice(bytes).map_err(|err| err.to_string())?;
+    Ok(game_state)
+}
+
+/// Queues a snapshot to be written to `path` once the current tick has finished.
+pub fn request_snapshot(game_state: &mut Game, path: String) {
+    game_state.snapshot_requests.push(path);
+}
+
+pub fn take_snapshot_requests(game_state: &mut Game) -> Vec<String> {
+    std::mem::take(&mut game_state.snapshot_requests)
+}
+
 pub fn upsert_body(game_state: &mut Game, body: &body::Body) -> bool {
-    let metadata = BodyMetadata {
+    let mut metadata = BodyMetadata {
         id: body.id.clone(),
         team_id: body.team_id.clone(),
         owner_id: body.owner_id.clone(),
         class: body.class,
         dimensions: body.dimensions,
         hp: body.hp,
+        max_hp: body.hp,
+        spawned_at_tick: game_state.tick,
+        respawn_at_tick: None,
+        pending_damage: 0.0,
+        pickup: body.pickup,
+        buffs: Vec::new(),
+        ammo: match body.class {
+            body::BodyClass::Player => body.ammo.or(game_state.rules.starting_ammo),
+            _ => None,
+        },
+        character: match body.class {
+            body::BodyClass::Player => Some(movement::init_character(
+                &game_state.movement,
+                body.linvel,
+                body.dimensions.2,
+            )),
+            _ => None,
+        },
+        aim: match body.class {
+            body::BodyClass::Player => Some(movement::get_aim(
+                &game_state.movement,
+                body.rotation.yaw(),
+                body.aim.map_or(0.0, |aim| aim.pitch),
+            )),
+            _ => None,
+        },
+        shape: body.shape.clone(),
+        destructible: body.destructible,
+        bot: None,
     };
 
     match game_state.handle_by_body_id.get(&body.id) {
         Some(existing_body_handle) => {
+            if let Some(existing_metadata) = game_state.metadata_by_handle.get(existing_body_handle)
+            {
+                // hp only changes through damage and respawns, which also switch the collision
+                // groups between player and ghost, so re-adding a player cannot bring it back
+                metadata.hp = existing_metadata.hp;
+                metadata.max_hp = existing_metadata.max_hp;
+                metadata.spawned_at_tick = existing_metadata.spawned_at_tick;
+                metadata.respawn_at_tick = existing_metadata.respawn_at_tick;
+                metadata.pending_damage = existing_metadata.pending_damage;
+                metadata.buffs = existing_metadata.buffs.clone();
+                metadata.ammo = existing_metadata.ammo;
+                metadata.bot = existing_metadata.bot.clone();
+                // the collider is not rebuilt, so a crouching player keeps its crouched height and
+                // can still stand back up to the height it had before
+                metadata.dimensions = existing_metadata.dimensions;
+                if let (Some(character), Some(existing_character)) = (
+                    metadata.character.as_mut(),
+                    existing_metadata.character.as_ref(),
+                ) {
+                    character.move_direction = existing_character.move_direction;
+                    character.grounded = existing_character.grounded;
+                    character.stamina = existing_character.stamina;
+                    character.wants_crouch = existing_character.wants_crouch;
+                    character.crouching = existing_character.crouching;
+                    character.standing_height = existing_character.standing_height;
+                    character.dash_cooldown_ticks = existing_character.dash_cooldown_ticks;
+                }
+                if let (Some(aim), Some(existing_aim), None) =
+                    (metadata.aim.as_mut(), existing_metadata.aim, body.aim)
+                {
+                    aim.pitch = existing_aim.pitch;
+                }
+            }
             if let Some(existing_body) =
                 physics_world::get_body_mut(&mut game_state.world, existing_body_handle)
             {
                 existing_body.set_translation(to_vec3(body.translation), true);
                 existing_body.set_linvel(to_vec3(body.linvel), true);
                 existing_body.set_angvel(to_vec3(body.angvel), true);
-                existing_body
-                    .set_rotation(Rotation::from_euler_angles(0.0, 0.0, body.rotation.2), true);
+                existing_body.set_rotation(body.rotation.to_quaternion(), true);
             }
             game_state
                 .metadata_by_handle
@@ -131,6 +687,9 @@ pub fn upsert_body(game_state: &mut Game, body: &body::Body) -> bool {
         }
         None => {
             let body_handle = physics_world::add_body(&mut game_state.world, body);
+            if matches!(body.class, body::BodyClass::Player) {
+                lift_above_floor(game_state, &body_handle, body);
+            }
             game_state.metadata_by_handle.insert(body_handle, metadata);
             game_state
                 .handle_by_body_id
@@ -140,22 +699,262 @@ pub fn upsert_body(game_state: &mut Game, body: &body::Body) -> bool {
     }
 }
 
-pub fn delete_body(body: body::Body, game_state: &mut Game) {
-    match game_state.handle_by_body_id.remove(&body.id) {
-        Some(handle) => {
-            physics_world::remove_body(&mut game_state.world, handle);
-            game_state.metadata_by_handle.remove(&handle);
+/// Players are placed by clients that assume a flat floor, which may put them inside hilly terrain.
+fn lift_above_floor(game_state: &mut Game, handle: &RigidBodyHandle, body: &body::Body) {
+    let (x, y, z) = body.translation;
+    let lowest_z = get_floor_height(game_state, x, y) + body.dimensions.2 / 2.0;
+    if z < lowest_z {
+        physics_world::teleport_body(
+            &mut game_state.world,
+            handle,
+            Vector3::new(x, y, lowest_z + SPAWN_CLEARANCE),
+        );
+    }
+}
+
+/// Adds a bot player to the team at a free spawn point and returns its handle. Bots are numbered
+/// in the order they join, so a replayed match gives them the same ids.
+pub fn add_bot(
+    game_state: &mut Game,
+    team_id: String,
+    difficulty: bot::Difficulty,
+) -> RigidBodyHandle {
+    let mut number = 1;
+    while game_state
+        .handle_by_body_id
+        .contains_key(&format!("bot-{}", number))
+    {
+        number += 1;
+    }
+
+    let translation = init::get_spawn_candidate(&mut game_state.rng, bot::BOT_HEIGHT);
+    let body = bot::create_body(format!("bot-{}", number), team_id, translation);
+    upsert_body(game_state, &body);
+    let handle = game_state.handle_by_body_id[&body.id];
+    if let Some(translation) = find_spawn_point(game_state, &handle, bot::BOT_HEIGHT) {
+        physics_world::teleport_body(&mut game_state.world, &handle, translation);
+    }
+
+    let seed = game_state.rng.gen();
+    if let Some(metadata) = game_state.metadata_by_handle.get_mut(&handle) {
+        metadata.bot = Some(bot::init(difficulty, seed));
+    }
+    handle
+}
+
+/// Lets every living bot look around and decide on its inputs for the coming tick. They are
+/// applied like any client's, and are left out of recordings since a replay comes up with them
+/// again.
+pub fn get_bot_inputs(game_state: &mut Game) -> Vec<UserInput> {
+    let mut handles: Vec<RigidBodyHandle> = game_state
+        .metadata_by_handle
+        .iter()
+        .filter(|(_handle, metadata)| metadata.bot.is_some() && metadata.hp > 0)
+        .map(|(handle, _metadata)| *handle)
+        .collect();
+    handles.sort_by_key(|handle| handle.into_raw_parts());
+
+    let mut inputs = Vec::new();
+    for handle in handles {
+        let perception = match perceive(game_state, &handle) {
+            Some(perception) => perception,
+            None => continue,
+        };
+        let metadata = match game_state.metadata_by_handle.get_mut(&handle) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        let grid = &game_state.nav;
+        if let Some(bot) = metadata.bot.as_mut() {
+            inputs.extend(bot::think(
+                bot,
+                &metadata.id,
+                metadata.team_id.as_deref(),
+                &perception,
+                |from, to| nav::find_path(grid, from, to),
+            ));
         }
-        None => {}
+    }
+    inputs
+}
+
+fn perceive(game_state: &Game, handle: &RigidBodyHandle) -> Option<bot::Perception> {
+    let metadata = game_state.metadata_by_handle.get(handle)?;
+    let bot = metadata.bot.as_ref()?;
+    let center = *physics_world::get_body(&game_state.world, handle)?.translation();
+    let feet = center - Vector3::z() * metadata.dimensions.2 / 2.0;
+    let eye = feet + Vector3::z() * metadata.dimensions.2 * 0.8;
+
+    Some(bot::Perception {
+        eye,
+        feet,
+        can_shoot: metadata.ammo != Some(0),
+        obstacle_ahead: get_obstacle_ahead(game_state, handle, feet, bot::get_heading(bot)),
+        target: find_target(
+            game_state,
+            handle,
+            metadata.team_id.as_ref(),
+            eye,
+            bot::get_difficulty(bot).sight_range(),
+        ),
+    })
+}
+
+fn get_obstacle_ahead(
+    game_state: &Game,
+    handle: &RigidBodyHandle,
+    feet: Vector3<f32>,
+    heading: Vector2<f32>,
+) -> Option<bot::Obstacle> {
+    let direction = Vector3::new(heading.x, heading.y, 0.0);
+    let is_blocked_at = |height: f32| {
+        physics_world::cast_sight_ray(
+            &game_state.world,
+            handle,
+            feet + Vector3::z() * height,
+            direction,
+            bot::PROBE_DISTANCE,
+        )
+        .is_some()
     };
+
+    match (
+        is_blocked_at(bot::KNEE_HEIGHT),
+        is_blocked_at(bot::CHEST_HEIGHT),
+    ) {
+        (_, true) => Some(bot::Obstacle::High),
+        (true, false) => Some(bot::Obstacle::Low),
+        (false, false) => None,
+    }
+}
+
+/// Finds the nearest living enemy within range that nothing stands in front of.
+fn find_target(
+    game_state: &Game,
+    handle: &RigidBodyHandle,
+    team_id: Option<&String>,
+    eye: Vector3<f32>,
+    sight_range: f32,
+) -> Option<bot::Target> {
+    let mut enemies: Vec<(&RigidBodyHandle, &BodyMetadata)> = game_state
+        .metadata_by_handle
+        .iter()
+        .filter(|(enemy_handle, metadata)| {
+            *enemy_handle != handle
+                && matches!(metadata.class, body::BodyClass::Player)
+                && metadata.hp > 0
+                && (team_id.is_none() || metadata.team_id.as_ref() != team_id)
+        })
+        .collect();
+    enemies.sort_by_key(|(enemy_handle, _metadata)| enemy_handle.into_raw_parts());
+
+    enemies
+        .into_iter()
+        .filter_map(|(enemy_handle, metadata)| {
+            let position = *physics_world::get_body(&game_state.world, enemy_handle)?.translation();
+            let offset = position - eye;
+            let distance = offset.norm();
+            if distance > sight_range {
+                return None;
+            }
+            let (seen_handle, _distance) = physics_world::cast_sight_ray(
+                &game_state.world,
+                handle,
+                eye,
+                offset.try_normalize(f32::EPSILON)?,
+                distance,
+            )?;
+            if seen_handle != *enemy_handle {
+                return None;
+            }
+            let velocity = metadata
+                .character
+                .as_ref()
+                .map_or_else(Vector3::zeros, |character| character.velocity);
+            Some((distance, bot::Target { position, velocity }))
+        })
+        .min_by(|(a, _a_target), (b, _b_target)| a.total_cmp(b))
+        .map(|(_distance, target)| target)
+}
+
+/// Adds a bullet fired by its owner, unless the owner is out of ammo or dead.
+pub fn shoot(game_state: &mut Game, bullet: &body::Body) -> bool {
+    if let Some(owner_handle) = bullet
+        .owner_id
+        .as_ref()
+        .and_then(|owner_id| game_state.handle_by_body_id.get(owner_id))
+        .copied()
+    {
+        if let Some(owner) = game_state.metadata_by_handle.get_mut(&owner_handle) {
+            if matches!(owner.class, body::BodyClass::Player) && owner.hp == 0 {
+                return false;
+            }
+            match owner.ammo {
+                Some(0) => return false,
+                Some(ammo) => owner.ammo = Some(ammo - 1),
+                None => {}
+            }
+        }
+    }
+
+    upsert_body(game_state, bullet);
+    true
+}
+
+pub fn delete_body(body: body::Body, game_state: &mut Game) {
+    if let Some(handle) = game_state.handle_by_body_id.remove(&body.id) {
+        physics_world::remove_body(&mut game_state.world, handle);
+        game_state.metadata_by_handle.remove(&handle);
+    }
 }
 
 pub fn get_handle(body_id: &String, game_state: &Game) -> RigidBodyHandle {
     game_state.handle_by_body_id[body_id]
 }
 
-pub fn is_won(game_state: &Game) -> bool {
-    get_num_teams_alive(&game_state.metadata_by_handle) == 1
+#[cfg(test)]
+pub fn get_body(game_state: &Game, body_id: &str) -> Option<body::Body> {
+    let handle = game_state.handle_by_body_id.get(body_id)?;
+    get_body_from_handle(game_state, handle)
+}
+
+/// The teams still in the match. Players waiting to respawn count, so that a team wiped out
+/// between respawn waves has not lost.
+pub fn get_teams_alive(game_state: &Game) -> HashSet<String> {
+    let mut teams_alive = get_alive_team_ids(game_state.metadata_by_handle.values());
+    teams_alive.extend(
+        game_state
+            .metadata_by_handle
+            .values()
+            .filter(|metadata| {
+                matches!(metadata.class, body::BodyClass::Player)
+                    && metadata.respawn_at_tick.is_some()
+            })
+            .filter_map(|metadata| metadata.team_id.clone()),
+    );
+    teams_alive
+}
+
+pub fn add_sensor_region(
+    game_state: &mut Game,
+    center: (f32, f32, f32),
+    half_extents: (f32, f32, f32),
+) -> ColliderHandle {
+    physics_world::add_sensor(
+        &mut game_state.world,
+        to_vec3(center),
+        to_vec3(half_extents),
+    )
+}
+
+/// Returns the teams with at least one living player inside the given sensor region.
+pub fn get_teams_in_region(game_state: &Game, sensor_handle: ColliderHandle) -> HashSet<String> {
+    let bodies_in_region = physics_world::get_intersecting_bodies(&game_state.world, sensor_handle);
+    get_alive_team_ids(
+        bodies_in_region
+            .iter()
+            .filter_map(|handle| game_state.metadata_by_handle.get(handle)),
+    )
 }
 
 pub fn get_body_from_handle(game_state: &Game, handle: &RigidBodyHandle) -> Option<body::Body> {
@@ -163,14 +962,42 @@ pub fn get_body_from_handle(game_state: &Game, handle: &RigidBodyHandle) -> Opti
         physics_world::get_body(&game_state.world, handle),
         game_state.metadata_by_handle.get(handle),
     ) {
-        (Some(rigid_body), Some(metadata)) => Some(rigid_body_to_body(rigid_body, &metadata)),
+        (Some(rigid_body), Some(metadata)) => {
+            let buffs = get_reported_buffs(game_state, metadata);
+            let dt = physics_world::get_dt(&game_state.world);
+            let movement = metadata
+                .character
+                .as_ref()
+                .map(|character| movement::get_status(character, dt));
+            Some(rigid_body_to_body(rigid_body, metadata, buffs, movement))
+        }
         _ => None,
     }
 }
 
-fn rigid_body_to_body(body: &RigidBody, metadata: &BodyMetadata) -> body::Body {
+fn get_reported_buffs(game_state: &Game, metadata: &BodyMetadata) -> Vec<pickup::Buff> {
+    let tick_ms = get_tick_ms(game_state);
+    metadata
+        .buffs
+        .iter()
+        .map(|buff| pickup::Buff {
+            kind: buff.kind,
+            seconds_remaining: (buff.expires_at_tick - game_state.tick) as f32 * tick_ms / 1000.0,
+        })
+        .collect()
+}
+
+fn rigid_body_to_body(
+    body: &RigidBody,
+    metadata: &BodyMetadata,
+    buffs: Vec<pickup::Buff>,
+    movement: Option<movement::MovementStatus>,
+) -> body::Body {
     let translation = body.translation();
-    let linvel = body.linvel();
+    let linvel = match &metadata.character {
+        Some(character) => character.velocity,
+        None => *body.linvel(),
+    };
     let angvel = body.angvel();
 
     body::Body {
@@ -178,45 +1005,38 @@ fn rigid_body_to_body(body: &RigidBody, metadata: &BodyMetadata) -> body::Body {
         team_id: metadata.team_id.clone(),
         owner_id: metadata.owner_id.clone(),
         translation: (translation.x, translation.y, translation.z),
-        rotation: (0.0, 0.0, rigid_body_rotation_to_angle_deg(body)),
+        rotation: EulerDegrees::from_quaternion(body.rotation()),
         linvel: (linvel.x, linvel.y, linvel.z),
         angvel: (angvel.x, angvel.y, angvel.z),
         dimensions: metadata.dimensions,
         mass: body.mass(),
         class: metadata.class,
         hp: metadata.hp,
+        pickup: metadata.pickup,
+        buffs,
+        ammo: metadata.ammo,
+        movement,
+        aim: metadata.aim,
+        shape: metadata.shape.clone(),
+        destructible: metadata.destructible,
     }
 }
 
-fn rigid_body_rotation_to_angle_deg(body: &RigidBody) -> f32 {
-    let rotation: UnitQuaternion<f32> = *body.rotation();
-    let angle_radians = rotation.angle();
-    let axis = rotation.axis().unwrap_or(Vector3::z_axis());
-
-    let sign = if axis.z > 0.0 { 1.0 } else { -1.0 };
-    let angle_degrees = sign * angle_radians * (180.0 / PI);
-
-    if angle_degrees < 0.0 {
-        angle_degrees + 360.0
-    } else {
-        angle_degrees
-    }
-}
-
-fn get_num_teams_alive(metadata_by_handle: &HashMap<RigidBodyHandle, BodyMetadata>) -> usize {
+fn get_alive_team_ids<'a>(bodies: impl Iterator<Item = &'a BodyMetadata>) -> HashSet<String> {
     let mut teams_alive = HashSet::new();
 
-    for (_id, body) in metadata_by_handle {
-        match (body.hp, &body.team_id) {
-            (_, None) => {}
-            (0, _) => {}
-            (_nonzero_hp, Some(team_id)) => {
-                teams_alive.insert(team_id);
+    for body in bodies {
+        match (body.class, body.hp, &body.team_id) {
+            (_, _, None) => {}
+            (_, 0, _) => {}
+            (body::BodyClass::Player, _nonzero_hp, Some(team_id)) => {
+                teams_alive.insert(team_id.clone());
             }
+            _ => {}
         };
     }
 
-    teams_alive.len()
+    teams_alive
 }
 
 fn get_metadata_for_collider_handle(
@@ -224,75 +1044,889 @@ fn get_metadata_for_collider_handle(
     game: &Game,
 ) -> Option<(RigidBodyHandle, BodyMetadata)> {
     let collider_set = physics_world::get_colliders(&game.world);
-    if let Some(body_handle) = collider_set
-        .get(collider_handle)
-        .map(|c| c.parent().unwrap())
-    {
+    if let Some(body_handle) = collider_set.get(collider_handle).and_then(|c| c.parent()) {
         if let Some(metadata) = game.metadata_by_handle.get(&body_handle) {
             return Some((body_handle, metadata.clone()));
         }
     }
 
-    return None;
+    None
 }
 
 pub fn handle_contact(collision_event: CollisionEvent, game_state: &mut Game) {
-    match collision_event {
-        CollisionEvent::Started(collider_handle_a, collider_handle_b, _flags) => {
-            let ((body_handle_a, metadata_a), (body_handle_b, metadata_b)) = match (
-                get_metadata_for_collider_handle(collider_handle_a, &game_state),
-                get_metadata_for_collider_handle(collider_handle_b, &game_state),
-            ) {
-                (Some(a), Some(b)) => (a, b),
-                _ => return,
-            };
+    if let CollisionEvent::Started(collider_handle_a, collider_handle_b, _flags) = collision_event {
+        let ((body_handle_a, metadata_a), (body_handle_b, metadata_b)) = match (
+            get_metadata_for_collider_handle(collider_handle_a, game_state),
+            get_metadata_for_collider_handle(collider_handle_b, game_state),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return,
+        };
 
-            let are_objects_on_same_team = metadata_a.team_id == metadata_b.team_id;
+        match (metadata_a.class, metadata_b.class) {
+            (body::BodyClass::Player, body::BodyClass::Bullet) => {
+                let hit_point = physics_world::get_contact_point(
+                    &game_state.world,
+                    collider_handle_a,
+                    collider_handle_b,
+                );
+                handle_player_bullet_collision(body_handle_a, body_handle_b, hit_point, game_state);
+            }
+            (body::BodyClass::Bullet, body::BodyClass::Player) => {
+                let hit_point = physics_world::get_contact_point(
+                    &game_state.world,
+                    collider_handle_a,
+                    collider_handle_b,
+                );
+                handle_player_bullet_collision(body_handle_b, body_handle_a, hit_point, game_state);
+            }
+            (body::BodyClass::Obstacle, body::BodyClass::Bullet) => {
+                handle_obstacle_bullet_collision(body_handle_a, body_handle_b, game_state);
+            }
+            (body::BodyClass::Bullet, body::BodyClass::Obstacle) => {
+                handle_obstacle_bullet_collision(body_handle_b, body_handle_a, game_state);
+            }
+            (body::BodyClass::Player, body::BodyClass::Pickup) => {
+                handle_player_pickup_collision(body_handle_a, body_handle_b, game_state);
+            }
+            (body::BodyClass::Pickup, body::BodyClass::Player) => {
+                handle_player_pickup_collision(body_handle_b, body_handle_a, game_state);
+            }
+            _ => {}
+        };
+    }
+}
 
-            match (metadata_a.class, metadata_b.class) {
-                (body::BodyClass::Player, body::BodyClass::Bullet) => {
-                    if !are_objects_on_same_team {
-                        handle_player_bullet_collision(body_handle_a, body_handle_b, game_state);
-                    }
+/// `hit_point` is where the bullet struck, if the contact was recorded. By the end of the step a
+/// fast bullet may already have bounced off, so its position then is only a fallback.
+fn handle_player_bullet_collision(
+    player_handle: RigidBodyHandle,
+    bullet_handle: RigidBodyHandle,
+    hit_point: Option<Vector3<f32>>,
+    game_state: &mut Game,
+) {
+    let (player, bullet) = match (
+        get_body_from_handle(game_state, &player_handle),
+        get_body_from_handle(game_state, &bullet_handle),
+    ) {
+        (Some(player), Some(bullet)) => (player, bullet),
+        _ => return,
+    };
+
+    let is_self_hit = bullet.owner_id.as_ref() == Some(&player.id);
+    let is_same_team = player.team_id == bullet.team_id;
+    let hit_point = hit_point
+        .map(|point| (point.x, point.y, point.z))
+        .unwrap_or(bullet.translation);
+    let region = body::get_hit_region(&player, hit_point);
+    let damage = match rules::bullet_damage(&game_state.rules, is_self_hit, is_same_team, region) {
+        Some(damage) => damage,
+        None => return,
+    };
+    let damage = damage * get_damage_multiplier(game_state, &bullet);
+
+    let tick_ms = get_tick_ms(game_state);
+    if let Some(body_data) = game_state.metadata_by_handle.get_mut(&player_handle) {
+        let ms_since_spawn = (game_state.tick - body_data.spawned_at_tick) as f32 * tick_ms;
+        let previous_hp = body_data.hp;
+        if body_data.hp > 0 && !rules::is_invulnerable(&game_state.rules, ms_since_spawn) {
+            apply_damage(body_data, damage);
+        }
+
+        if body_data.hp < previous_hp {
+            game_state.events.push(GameEvent::Hit {
+                id: player.id.clone(),
+                team_id: player.team_id.clone(),
+                attacker_id: bullet.owner_id.clone(),
+                attacker_team_id: bullet.team_id.clone(),
+                hp: body_data.hp,
+            });
+        }
+
+        if previous_hp > 0 && body_data.hp == 0 {
+            physics_world::set_collision_groups(
+                &mut game_state.world,
+                &player_handle,
+                body::get_ghost_collision_groups(),
+            );
+            if let Some(character) = body_data.character.as_mut() {
+                character.move_direction = (0.0, 0.0);
+                character.velocity.x = 0.0;
+                character.velocity.y = 0.0;
+            }
+            game_state.events.push(GameEvent::Eliminated {
+                id: player.id.clone(),
+                team_id: player.team_id.clone(),
+                attacker_id: bullet.owner_id.clone(),
+                attacker_team_id: bullet.team_id.clone(),
+            });
+
+            body_data.respawn_at_tick =
+                rules::get_respawn_tick(&game_state.rules, game_state.tick, tick_ms);
+            if let Some(respawn_at_tick) = body_data.respawn_at_tick {
+                let seconds_remaining =
+                    ((respawn_at_tick - game_state.tick) as f32 * tick_ms / 1000.0).ceil() as u64;
+                game_state.events.push(GameEvent::RespawnCountdown {
+                    id: player.id.clone(),
+                    seconds_remaining,
+                });
+            }
+        }
+    }
+
+    delete_body(bullet, game_state);
+}
+
+fn get_damage_multiplier(game_state: &Game, bullet: &body::Body) -> f32 {
+    bullet
+        .owner_id
+        .as_ref()
+        .and_then(|owner_id| game_state.handle_by_body_id.get(owner_id))
+        .and_then(|owner_handle| game_state.metadata_by_handle.get(owner_handle))
+        .map_or(1.0, |owner| pickup::get_damage_multiplier(&owner.buffs))
+}
+
+/// Bullets wear down destructible obstacles and are used up doing so. They bounce off anything
+/// else as before.
+fn handle_obstacle_bullet_collision(
+    obstacle_handle: RigidBodyHandle,
+    bullet_handle: RigidBodyHandle,
+    game_state: &mut Game,
+) {
+    let (obstacle, bullet) = match (
+        game_state.metadata_by_handle.get(&obstacle_handle),
+        get_body_from_handle(game_state, &bullet_handle),
+    ) {
+        (Some(obstacle), Some(bullet)) if obstacle.destructible => (obstacle, bullet),
+        _ => return,
+    };
+    let obstacle_id = obstacle.id.clone();
+
+    let damage = game_state.rules.bullet_damage * get_damage_multiplier(game_state, &bullet);
+    if let Some(body_data) = game_state.metadata_by_handle.get_mut(&obstacle_handle) {
+        let previous_hp = body_data.hp;
+        apply_damage(body_data, damage);
+
+        if body_data.hp < previous_hp {
+            game_state.events.push(GameEvent::Hit {
+                id: obstacle_id.clone(),
+                team_id: None,
+                attacker_id: bullet.owner_id.clone(),
+                attacker_team_id: bullet.team_id.clone(),
+                hp: body_data.hp,
+            });
+        }
+
+        if body_data.hp == 0 {
+            game_state.events.push(GameEvent::BodyRemoved {
+                id: obstacle_id.clone(),
+                removed_by: bullet.owner_id.clone(),
+            });
+            if let Some(handle) = game_state.handle_by_body_id.remove(&obstacle_id) {
+                physics_world::remove_body(&mut game_state.world, handle);
+                game_state.metadata_by_handle.remove(&handle);
+            }
+        }
+    }
+
+    delete_body(bullet, game_state);
+}
+
+fn handle_player_pickup_collision(
+    player_handle: RigidBodyHandle,
+    pickup_handle: RigidBodyHandle,
+    game_state: &mut Game,
+) {
+    let (pickup_id, pickup) = match game_state.metadata_by_handle.get(&pickup_handle) {
+        Some(BodyMetadata {
+            id,
+            pickup: Some(pickup),
+            respawn_at_tick: None,
+            ..
+        }) => (id.clone(), *pickup),
+        _ => return,
+    };
+
+    let tick = game_state.tick;
+    let ticks_per_second = 1000.0 / get_tick_ms(game_state);
+    let player_id = match game_state.metadata_by_handle.get_mut(&player_handle) {
+        Some(player) if player.hp > 0 => {
+            match pickup.effect {
+                pickup::PickupEffect::Health { amount } => {
+                    player.hp = (player.hp + amount).min(player.max_hp);
                 }
-                (body::BodyClass::Bullet, body::BodyClass::Player) => {
-                    if !are_objects_on_same_team {
-                        handle_player_bullet_collision(body_handle_b, body_handle_a, game_state);
+                pickup::PickupEffect::Ammo { amount } => {
+                    player.ammo = player.ammo.map(|ammo| ammo + amount);
+                }
+                effect => {
+                    if let Some((kind, duration_seconds)) = pickup::get_buff(effect) {
+                        let expires_at_tick =
+                            tick + (duration_seconds * ticks_per_second).ceil() as u64;
+                        pickup::add_buff(&mut player.buffs, kind, expires_at_tick);
                     }
                 }
-                _ => {}
             };
+            player.id.clone()
         }
-        _ => {}
+        _ => return,
     };
+
+    physics_world::set_colliders_enabled(&mut game_state.world, &pickup_handle, false);
+    if let Some(metadata) = game_state.metadata_by_handle.get_mut(&pickup_handle) {
+        metadata.respawn_at_tick =
+            Some(tick + (pickup.respawn_seconds * ticks_per_second).ceil() as u64);
+    }
+    game_state.events.push(GameEvent::PickupCollected {
+        id: pickup_id,
+        player_id,
+        effect: pickup.effect,
+    });
 }
 
-fn handle_player_bullet_collision(
-    player_handle: RigidBodyHandle,
-    bullet_handle: RigidBodyHandle,
-    game_state: &mut Game,
-) {
-    if let Some(body_data) = game_state.metadata_by_handle.get_mut(&player_handle) {
-        body_data.hp = (body_data.hp - 1).max(0);
-        physics_world::remove_body(&mut game_state.world, bullet_handle);
-        game_state.metadata_by_handle.remove(&bullet_handle);
+fn apply_damage(body_data: &mut BodyMetadata, damage: f32) {
+    let damage = pickup::absorb_damage(&mut body_data.buffs, damage);
+    body_data.pending_damage += damage;
+    let whole_damage = body_data.pending_damage.floor();
+    body_data.pending_damage -= whole_damage;
+    body_data.hp = (body_data.hp - whole_damage as i32).max(0);
+}
+
+/// Looks up the handle of a body that may be controlled by input, i.e. anything but a dead player.
+fn get_controllable_handle(game_state: &Game, id: &str) -> Option<RigidBodyHandle> {
+    let handle = game_state.handle_by_body_id.get(id)?;
+    match game_state.metadata_by_handle.get(handle) {
+        Some(metadata) if matches!(metadata.class, body::BodyClass::Player) && metadata.hp == 0 => {
+            None
+        }
+        _ => Some(*handle),
     }
 }
 
-pub fn move_body(game_state: &mut Game, id: &str, x: f32, y: f32) {
-    if let Some(handle) = game_state.handle_by_body_id.get(id) {
-        physics_world::move_body(&mut game_state.world, handle, x, y);
+fn get_character_mut<'a>(
+    game_state: &'a mut Game,
+    id: &str,
+) -> Option<&'a mut movement::CharacterState> {
+    let handle = get_controllable_handle(game_state, id)?;
+    game_state
+        .metadata_by_handle
+        .get_mut(&handle)?
+        .character
+        .as_mut()
+}
+
+pub fn move_body(game_state: &mut Game, id: &str, direction: (f32, f32)) {
+    if let Some(character) = get_character_mut(game_state, id) {
+        character.move_direction = direction;
     }
 }
 
 pub fn rotate_body(game_state: &mut Game, id: &str, rotation_angle: f32) {
-    if let Some(handle) = game_state.handle_by_body_id.get(id) {
-        physics_world::rotate_body(&mut game_state.world, handle, rotation_angle);
+    let pitch = get_aim(game_state, id).map_or(0.0, |aim| aim.pitch);
+    aim_body(game_state, id, rotation_angle, pitch);
+}
+
+/// Points a player's aim, turning the body to the aim's yaw while keeping it upright.
+pub fn aim_body(game_state: &mut Game, id: &str, yaw: f32, pitch: f32) {
+    let handle = match get_controllable_handle(game_state, id) {
+        Some(handle) => handle,
+        None => return,
+    };
+    let aim = movement::get_aim(&game_state.movement, yaw, pitch);
+
+    physics_world::rotate_body(&mut game_state.world, &handle, aim.yaw);
+    if let Some(metadata) = game_state.metadata_by_handle.get_mut(&handle) {
+        if metadata.aim.is_some() {
+            metadata.aim = Some(aim);
+        }
     }
 }
 
-pub fn jump_body(game_state: &mut Game, id: &str, linvel_z: f32) {
-    if let Some(handle) = game_state.handle_by_body_id.get(id) {
-        physics_world::jump_body(&mut game_state.world, handle, linvel_z);
+fn get_aim(game_state: &Game, id: &str) -> Option<movement::Aim> {
+    let handle = game_state.handle_by_body_id.get(id)?;
+    game_state.metadata_by_handle.get(handle)?.aim
+}
+
+pub fn jump_body(game_state: &mut Game, id: &str) {
+    if let Some(character) = get_character_mut(game_state, id) {
+        movement::request_jump(character);
+    }
+}
+
+pub fn set_sprint(game_state: &mut Game, id: &str, active: bool) {
+    if let Some(character) = get_character_mut(game_state, id) {
+        character.wants_sprint = active;
+    }
+}
+
+pub fn set_crouch(game_state: &mut Game, id: &str, active: bool) {
+    if let Some(character) = get_character_mut(game_state, id) {
+        character.wants_crouch = active;
+    }
+}
+
+pub fn dash_body(game_state: &mut Game, id: &str) {
+    if let Some(character) = get_character_mut(game_state, id) {
+        character.dash_requested = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fixtures;
+    use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+    use std::collections::BTreeSet;
+
+    fn add_player(game_state: &mut Game, id: &str, team_id: &str, translation: (f32, f32, f32)) {
+        upsert_body(
+            game_state,
+            &body::create_player(id, team_id, translation, 10),
+        );
+    }
+
+    fn get_metadata<'a>(game_state: &'a Game, id: &str) -> &'a BodyMetadata {
+        &game_state.metadata_by_handle[&game_state.handle_by_body_id[id]]
+    }
+
+    fn get_character<'a>(game_state: &'a Game, id: &str) -> &'a movement::CharacterState {
+        get_metadata(game_state, id).character.as_ref().unwrap()
+    }
+
+    fn create_obstacle(
+        id: &str,
+        translation: (f32, f32, f32),
+        dimensions: (f32, f32, f32),
+    ) -> body::Body {
+        body::Body {
+            id: id.to_string(),
+            team_id: None,
+            owner_id: None,
+            translation,
+            rotation: EulerDegrees::default(),
+            linvel: (0.0, 0.0, 0.0),
+            angvel: (0.0, 0.0, 0.0),
+            dimensions,
+            mass: 100.0,
+            class: body::BodyClass::Obstacle,
+            hp: 0,
+            pickup: None,
+            buffs: Vec::new(),
+            ammo: None,
+            movement: None,
+            aim: None,
+            shape: None,
+            destructible: false,
+        }
+    }
+
+    // a slab whose underside is above a crouching player's head but below a standing one's
+    fn add_ceiling(game_state: &mut Game, id: &str, (x, y): (f32, f32)) {
+        upsert_body(
+            game_state,
+            &create_obstacle(id, (x, y, 2.0), (4.0, 4.0, 1.0)),
+        );
+    }
+
+    fn get_positions(game_state: &Game) -> Vec<(String, (f32, f32, f32))> {
+        let mut positions: Vec<(String, (f32, f32, f32))> = get_handles(game_state)
+            .iter()
+            .filter_map(|handle| get_body_from_handle(game_state, handle))
+            .map(|body| (body.id, body.translation))
+            .collect();
+        positions.sort_by(|a, b| a.0.cmp(&b.0));
+        positions
+    }
+
+    #[test]
+    fn a_restored_game_carries_on_exactly_where_it_left_off() {
+        let mut original = init(&config::Config::default(), 0);
+        add_player(&mut original, "a", "red", (0.0, 0.0, 1.0));
+        add_player(&mut original, "b", "blue", (10.0, 0.0, 1.0));
+        move_body(&mut original, "a", (1.0, 0.5));
+        for _ in 0..30 {
+            step(&mut original);
+        }
+
+        let mut restored = restore(&snapshot(&original).unwrap()).unwrap();
+        assert_eq!(state_hash(&restored), state_hash(&original));
+        assert_eq!(get_positions(&restored), get_positions(&original));
+
+        jump_body(&mut original, "b");
+        jump_body(&mut restored, "b");
+        for _ in 0..30 {
+            step(&mut original);
+            step(&mut restored);
+        }
+        assert_eq!(state_hash(&restored), state_hash(&original));
+        assert_eq!(get_positions(&restored), get_positions(&original));
+    }
+
+    #[test]
+    fn a_player_re_added_while_crouching_can_still_stand_up() {
+        let mut game_state = init_empty(&config::Config::default(), 0);
+        add_player(&mut game_state, "a", "red", (0.0, 0.0, 1.0));
+        for _ in 0..30 {
+            step(&mut game_state);
+        }
+        set_crouch(&mut game_state, "a", true);
+        step(&mut game_state);
+        assert!(get_character(&game_state, "a").crouching);
+
+        let crouched = get_body(&game_state, "a").unwrap();
+        upsert_body(&mut game_state, &crouched);
+        assert!(get_character(&game_state, "a").crouching);
+
+        set_crouch(&mut game_state, "a", false);
+        step(&mut game_state);
+        assert!(!get_character(&game_state, "a").crouching);
+        assert_eq!(
+            get_metadata(&game_state, "a").dimensions.2,
+            body::PLAYER_HEIGHT
+        );
+        assert_eq!(
+            get_character(&game_state, "a").standing_height,
+            body::PLAYER_HEIGHT
+        );
+    }
+
+    #[test]
+    fn a_crouching_player_only_stands_up_once_there_is_headroom() {
+        let mut game_state = init_empty(&config::Config::default(), 0);
+        add_player(&mut game_state, "a", "red", (0.0, 0.0, 1.0));
+        for _ in 0..30 {
+            step(&mut game_state);
+        }
+        set_crouch(&mut game_state, "a", true);
+        step(&mut game_state);
+        add_ceiling(&mut game_state, "ceiling", (0.0, 0.0));
+        step(&mut game_state);
+
+        set_crouch(&mut game_state, "a", false);
+        for _ in 0..10 {
+            step(&mut game_state);
+        }
+        assert!(get_character(&game_state, "a").crouching);
+
+        let ceiling = get_body(&game_state, "ceiling").unwrap();
+        delete_body(ceiling, &mut game_state);
+        step(&mut game_state);
+        step(&mut game_state);
+        assert!(!get_character(&game_state, "a").crouching);
+    }
+
+    #[test]
+    fn a_dash_bursts_forward_and_then_cools_down() {
+        let config = config::Config::default();
+        let mut game_state = init_empty(&config, 0);
+        add_player(&mut game_state, "a", "red", (0.0, 0.0, 1.0));
+        for _ in 0..30 {
+            step(&mut game_state);
+        }
+        move_body(&mut game_state, "a", (1.0, 0.0));
+        dash_body(&mut game_state, "a");
+        step(&mut game_state);
+
+        let dash_speed = get_character(&game_state, "a").velocity.x;
+        assert!((dash_speed - config.movement.dash_speed).abs() < 0.1);
+        assert!(get_character(&game_state, "a").dash_cooldown_ticks > 0);
+
+        for _ in 0..5 {
+            step(&mut game_state);
+        }
+        let slowed_speed = get_character(&game_state, "a").velocity.x;
+        assert!(slowed_speed < dash_speed);
+        dash_body(&mut game_state, "a");
+        step(&mut game_state);
+        assert!(get_character(&game_state, "a").velocity.x < slowed_speed);
+    }
+
+    #[test]
+    fn bullets_wear_down_destructible_obstacles_until_they_are_removed() {
+        let mut game_state = init_empty(&config::Config::default(), 0);
+        add_player(&mut game_state, "a", "red", (0.0, 0.0, 1.0));
+        upsert_body(
+            &mut game_state,
+            &body::Body {
+                hp: 2,
+                destructible: true,
+                ..create_obstacle("crate", (5.0, 0.0, 1.0), (2.0, 2.0, 2.0))
+            },
+        );
+
+        let mut events = Vec::new();
+        for bullet in 0..2 {
+            shoot(
+                &mut game_state,
+                &body::create_bullet(
+                    &format!("bullet-{}", bullet),
+                    "a",
+                    Some("red"),
+                    (1.0, 0.0, 1.0),
+                    (40.0, 0.0, 0.0),
+                ),
+            );
+            for _ in 0..20 {
+                step(&mut game_state);
+                events.extend(take_events(&mut game_state));
+            }
+        }
+
+        let crate_hp: Vec<i32> = events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::Hit { id, hp, .. } if id == "crate" => Some(*hp),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(crate_hp, vec![1, 0]);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            GameEvent::BodyRemoved { id, removed_by: Some(removed_by) }
+                if id == "crate" && removed_by == "a"
+        )));
+        assert!(get_body(&game_state, "crate").is_none());
+        assert!(get_body(&game_state, "bullet-1").is_none());
+    }
+
+    #[test]
+    fn generated_obstacles_are_only_destructible_when_configured() {
+        let count_destructible = |config: &config::Config| {
+            let game_state = init(config, 5);
+            game_state
+                .metadata_by_handle
+                .values()
+                .filter(|metadata| metadata.destructible)
+                .count()
+        };
+
+        assert_eq!(count_destructible(&config::Config::default()), 0);
+        let config = config::Config {
+            destructible_obstacle_ratio: 0.5,
+            ..config::Config::default()
+        };
+        assert!(count_destructible(&config) > 0);
+    }
+
+    #[test]
+    fn explosions_push_props_away_harder_the_closer_they_are() {
+        let mut game_state = init_empty(&config::Config::default(), 0);
+        let prop = |id: &str, x: f32| body::Body {
+            class: body::BodyClass::Prop,
+            mass: 20.0,
+            ..create_obstacle(id, (x, 0.0, 0.5), (1.0, 1.0, 1.0))
+        };
+        upsert_body(&mut game_state, &prop("near", 2.0));
+        upsert_body(&mut game_state, &prop("far", 4.0));
+        upsert_body(&mut game_state, &prop("outside", 10.0));
+        upsert_body(
+            &mut game_state,
+            &create_obstacle("wall", (0.0, 2.0, 1.0), (1.0, 1.0, 2.0)),
+        );
+        add_player(&mut game_state, "a", "red", (0.0, -2.0, 1.0));
+        // brings the scene queries up to date with the bodies
+        step(&mut game_state);
+
+        let pushed: Vec<String> = explode(&mut game_state, (0.0, 0.0, 0.5), 5.0, 50.0)
+            .iter()
+            .map(|handle| game_state.metadata_by_handle[handle].id.clone())
+            .collect();
+
+        // the player is moved by its character controller and the wall is fixed, so neither is
+        // pushed
+        assert_eq!(pushed.len(), 2);
+        assert!(pushed.contains(&"near".to_string()) && pushed.contains(&"far".to_string()));
+        let near = get_body(&game_state, "near").unwrap().linvel;
+        let far = get_body(&game_state, "far").unwrap().linvel;
+        assert!(near.0 > far.0 && far.0 > 0.0, "{:?} {:?}", near, far);
+        assert_eq!(get_body(&game_state, "outside").unwrap().linvel.0, 0.0);
+        assert_eq!(
+            get_body(&game_state, "wall").unwrap().linvel,
+            (0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn bots_find_and_shoot_an_enemy_in_sight() {
+        let mut game_state = init(&config::Config::default(), 4);
+        let bot_handle = add_bot(&mut game_state, "red".to_string(), bot::Difficulty::Hard);
+        let (x, y) = fixtures::OPEN_GROUND;
+        physics_world::teleport_body(&mut game_state.world, &bot_handle, Vector3::new(x, y, 1.1));
+        add_player(&mut game_state, "a", "blue", (x, y - 20.0, 1.0));
+
+        let mut hit_by = None;
+        for _ in 0..120 {
+            let inputs = get_bot_inputs(&mut game_state);
+            crate::user_input::apply_user_input(&inputs, &mut game_state);
+            step(&mut game_state);
+            for event in take_events(&mut game_state) {
+                if let GameEvent::Hit {
+                    id, attacker_id, ..
+                } = event
+                {
+                    hit_by.get_or_insert((id, attacker_id));
+                }
+            }
+        }
+
+        assert_eq!(hit_by, Some(("a".to_string(), Some("bot-1".to_string()))));
+    }
+
+    #[test]
+    fn paths_lead_across_the_arena_without_running_into_obstacles() {
+        let mut game_state = init(&config::Config::default(), 6);
+        let floor = game_state.handle_by_body_id[init::FLOOR_ID];
+        // brings the scene queries up to date with the arena
+        step(&mut game_state);
+
+        let path = find_path(&game_state, (-80.0, -80.0, 0.0), (80.0, 70.0, 0.0)).unwrap();
+        assert_eq!(path.last(), Some(&(80.0, 70.0, 0.0)));
+        let mut previous = Vector3::new(-80.0, -80.0, 0.0);
+        for (x, y, z) in path {
+            let waypoint = Vector3::new(x, y, z);
+            let leg = waypoint - previous;
+            for height in [bot::KNEE_HEIGHT, bot::CHEST_HEIGHT] {
+                let blocked_by = physics_world::cast_sight_ray(
+                    &game_state.world,
+                    &floor,
+                    previous + Vector3::z() * height,
+                    leg.normalize(),
+                    leg.norm(),
+                );
+                // props are left out of the grid, since they are pushed aside
+                let blocked_by_obstacle = blocked_by.is_some_and(|(handle, _distance)| {
+                    !matches!(
+                        game_state.metadata_by_handle[&handle].class,
+                        body::BodyClass::Prop
+                    )
+                });
+                assert!(!blocked_by_obstacle, "the leg to {:?} is blocked", waypoint);
+            }
+            previous = waypoint;
+        }
+    }
+
+    #[test]
+    fn the_same_seed_builds_the_same_arena() {
+        let config = config::Config::default();
+
+        assert_eq!(
+            state_hash(&init(&config, 12)),
+            state_hash(&init(&config, 12))
+        );
+        assert_ne!(
+            state_hash(&init(&config, 12)),
+            state_hash(&init(&config, 13))
+        );
+    }
+
+    #[test]
+    fn the_state_hash_ignores_jitter_but_not_game_state() {
+        let mut game_state = init(&config::Config::default(), 3);
+        add_player(&mut game_state, "a", "red", (0.0, 0.0, 1.0));
+        let hash = state_hash(&game_state);
+        let handle = game_state.handle_by_body_id["a"];
+
+        physics_world::teleport_body(&mut game_state.world, &handle, Vector3::new(1e-6, 0.0, 1.0));
+        assert_eq!(state_hash(&game_state), hash);
+
+        game_state.metadata_by_handle.get_mut(&handle).unwrap().hp -= 1;
+        assert_ne!(state_hash(&game_state), hash);
+    }
+
+    #[test]
+    fn state_hashes_are_reported_at_the_configured_interval() {
+        let config = config::Config {
+            state_hash_interval_ticks: Some(3),
+            ..config::Config::default()
+        };
+        let mut game_state = init(&config, 3);
+
+        let mut hash_ticks = Vec::new();
+        for _ in 0..7 {
+            step(&mut game_state);
+            for event in take_events(&mut game_state) {
+                if let GameEvent::StateHash { tick, hash } = event {
+                    assert_eq!(hash, state_hash(&game_state));
+                    hash_ticks.push(tick);
+                }
+            }
+        }
+        assert_eq!(hash_ticks, vec![3, 6]);
+    }
+
+    /// Collects every path through a serialised value down to its leaves along with the kind of
+    /// each leaf, e.g. `.tick: int`. The elements of sequences and the values of maps not keyed by
+    /// name are merged, so that the paths describe the layout rather than the contents.
+    struct LayoutPaths<'a> {
+        prefix: String,
+        paths: &'a mut BTreeSet<String>,
+    }
+
+    impl LayoutPaths<'_> {
+        fn leaf<E>(self, kind: &str) -> Result<(), E> {
+            self.paths.insert(format!("{}: {}", self.prefix, kind));
+            Ok(())
+        }
+
+        fn child(&mut self, name: &str) -> LayoutPaths<'_> {
+            LayoutPaths {
+                prefix: format!("{}.{}", self.prefix, name),
+                paths: self.paths,
+            }
+        }
+    }
+
+    impl<'de> DeserializeSeed<'de> for LayoutPaths<'_> {
+        type Value = ();
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for LayoutPaths<'_> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("any value")
+        }
+
+        fn visit_bool<E>(self, _value: bool) -> Result<(), E> {
+            self.leaf("bool")
+        }
+
+        fn visit_i64<E>(self, _value: i64) -> Result<(), E> {
+            self.leaf("int")
+        }
+
+        fn visit_u64<E>(self, _value: u64) -> Result<(), E> {
+            self.leaf("int")
+        }
+
+        fn visit_f32<E>(self, _value: f32) -> Result<(), E> {
+            self.leaf("f32")
+        }
+
+        fn visit_f64<E>(self, _value: f64) -> Result<(), E> {
+            self.leaf("f64")
+        }
+
+        fn visit_str<E>(self, _value: &str) -> Result<(), E> {
+            self.leaf("str")
+        }
+
+        fn visit_bytes<E>(self, _value: &[u8]) -> Result<(), E> {
+            self.leaf("bytes")
+        }
+
+        fn visit_unit<E>(self) -> Result<(), E> {
+            self.leaf("nil")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+            while seq.next_element_seed(self.child("[]"))?.is_some() {}
+            self.leaf("seq")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+            while let Some(KeyName(name)) = map.next_key()? {
+                map.next_value_seed(self.child(&name))?;
+            }
+            self.leaf("map")
+        }
+    }
+
+    // map keys that are not names, such as body handles, all stand for the same place
+    struct KeyName(String);
+
+    impl<'de> Deserialize<'de> for KeyName {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(KeyNameVisitor)
+        }
+    }
+
+    struct KeyNameVisitor;
+
+    impl<'de> Visitor<'de> for KeyNameVisitor {
+        type Value = KeyName;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a map key")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<KeyName, E> {
+            Ok(KeyName(value.to_string()))
+        }
+
+        fn visit_i64<E>(self, _value: i64) -> Result<KeyName, E> {
+            Ok(KeyName(String::from("{key}")))
+        }
+
+        fn visit_u64<E>(self, _value: u64) -> Result<KeyName, E> {
+            Ok(KeyName(String::from("{key}")))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyName, A::Error> {
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            Ok(KeyName(String::from("{key}")))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<KeyName, A::Error> {
+            while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+            Ok(KeyName(String::from("{key}")))
+        }
+    }
+
+    // every path a snapshot of a player, a bot and an obstacle holds, rapier's own included, has
+    // to be listed under the current version, so that a changed layout cannot go in without a bump
+    #[test]
+    fn the_snapshot_layout_matches_its_version() {
+        let mut game_state = init_empty(&config::Config::default(), 0);
+        add_player(&mut game_state, "a", "red", (0.0, 0.0, 1.0));
+        add_bot(&mut game_state, "blue".to_string(), bot::Difficulty::Normal);
+        upsert_body(
+            &mut game_state,
+            &create_obstacle("crate", (10.0, 10.0, 1.0), (2.0, 2.0, 2.0)),
+        );
+        for _ in 0..10 {
+            let inputs = get_bot_inputs(&mut game_state);
+            crate::user_input::apply_user_input(&inputs, &mut game_state);
+            step(&mut game_state);
+        }
+
+        let bytes = snapshot(&game_state).unwrap();
+        let mut paths = BTreeSet::new();
+        LayoutPaths {
+            prefix: String::new(),
+            paths: &mut paths,
+        }
+        .deserialize(&mut rmp_serde::Deserializer::from_read_ref(&bytes))
+        .unwrap();
+
+        let mut expected = include_str!("tests/snapshot_layout.txt").lines();
+        assert_eq!(
+            expected.next(),
+            Some(format!("version {}", SNAPSHOT_VERSION).as_str()),
+            "snapshot_layout.txt lists the layout of another version"
+        );
+        let expected = expected.map(String::from).collect::<BTreeSet<_>>();
+        let added = paths.difference(&expected).collect::<Vec<_>>();
+        let removed = expected.difference(&paths).collect::<Vec<_>>();
+        assert!(
+            added.is_empty() && removed.is_empty(),
+            "the snapshot layout changed, bump SNAPSHOT_VERSION and update snapshot_layout.txt\n\
+             added: {:#?}\nremoved: {:#?}",
+            added,
+            removed
+        );
+    }
+
+    #[test]
+    fn snapshots_from_another_version_are_rejected() {
+        let game_state = init(&config::Config::default(), 0);
+        let bytes = rmp_serde::to_vec_named(&(SNAPSHOT_VERSION + 1, &game_state)).unwrap();
+
+        let err = restore(&bytes).err().unwrap();
+        assert!(err.contains("Unsupported snapshot version"), "{}", err);
     }
 }
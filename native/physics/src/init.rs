@@ -1,71 +1,504 @@
 use crate::body;
+use crate::pickup;
+use crate::rotation::EulerDegrees;
+use crate::shape::ShapeDescriptor;
+use crate::terrain::{self, FloorConfig, TerrainConfig};
 use rand::Rng;
-use uuid::Uuid;
+use uuid::{Builder, Variant, Version};
 
-const ARENA_WIDTH: f32 = 200.0;
+pub const FLOOR_ID: &str = "floor";
+pub const ARENA_WIDTH: f32 = 200.0;
+// generated bodies keep this far from the edges of the arena
+const ARENA_MARGIN: f32 = 25.0;
 const MAX_OBSTACLES: usize = 500;
+const MAX_PICKUPS: usize = 30;
+const PICKUP_SIZE: f32 = 1.0;
+const SPAWN_RING_RADIUS: f32 = 50.0;
+const RAMP_PROBABILITY: f64 = 0.1;
+const PILLAR_PROBABILITY: f64 = 0.1;
+const DESTRUCTIBLE_HP: i32 = 20;
+const MAX_PROPS: usize = 40;
+const CRATE_SIZE: f32 = 1.0;
+const CRATE_MASS: f32 = 20.0;
+const BARREL_RADIUS: f32 = 0.4;
+const BARREL_HEIGHT: f32 = 1.2;
+const BARREL_MASS: f32 = 15.0;
 
-fn create_floor() -> body::Body {
+// ids come from the seeded generator too, so that a seed always rebuilds the same arena
+fn get_random_id(rng: &mut impl Rng) -> String {
+    Builder::from_bytes(rng.gen())
+        .set_variant(Variant::RFC4122)
+        .set_version(Version::Random)
+        .build()
+        .to_string()
+}
+
+pub fn create_floor(floor: &FloorConfig) -> body::Body {
+    match floor {
+        FloorConfig::Flat => create_flat_floor(),
+        FloorConfig::Heightfield(terrain) => create_terrain_floor(terrain),
+    }
+}
+
+fn create_flat_floor() -> body::Body {
     body::Body {
-        id: String::from("floor"),
+        id: String::from(FLOOR_ID),
         team_id: None,
         owner_id: None,
         translation: (0.0, 0.0, -0.5),
-        rotation: (0.0, 0.0, 0.0),
+        rotation: EulerDegrees::default(),
         linvel: (0.0, 0.0, 0.0),
         angvel: (0.0, 0.0, 0.0),
         dimensions: (ARENA_WIDTH + 0.1, ARENA_WIDTH + 0.1, 1.0),
         mass: 0.0,
         class: body::BodyClass::Obstacle,
         hp: 0,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: None,
+        destructible: false,
+    }
+}
+
+fn create_terrain_floor(terrain: &TerrainConfig) -> body::Body {
+    body::Body {
+        id: String::from(FLOOR_ID),
+        team_id: None,
+        owner_id: None,
+        translation: (0.0, 0.0, 0.0),
+        rotation: EulerDegrees::default(),
+        linvel: (0.0, 0.0, 0.0),
+        angvel: (0.0, 0.0, 0.0),
+        dimensions: (ARENA_WIDTH, ARENA_WIDTH, terrain.amplitude * 2.0),
+        mass: 0.0,
+        class: body::BodyClass::Obstacle,
+        hp: 0,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: Some(ShapeDescriptor::Heightfield {
+            heights: terrain::get_heights(terrain, ARENA_WIDTH),
+            size: (ARENA_WIDTH, ARENA_WIDTH),
+        }),
+        destructible: false,
     }
 }
 
-fn seed_obstacle_in_open_space(bodies: &mut Vec<body::Body>) {
-    let obstacle = create_random_obstacle();
+/// Returns the height of the floor at a point, ignoring any obstacles.
+pub fn get_floor_height(floor: &FloorConfig, x: f32, y: f32) -> f32 {
+    match floor {
+        FloorConfig::Flat => 0.0,
+        FloorConfig::Heightfield(terrain) => terrain::get_height(terrain, x, y),
+    }
+}
+
+const FOOTPRINT_SAMPLES: usize = 8;
+
+fn get_footprint_floor_heights<'a>(
+    body: &body::Body,
+    floor: &'a FloorConfig,
+) -> impl Iterator<Item = f32> + 'a {
+    let (x, y, _z) = body.translation;
+    let radius = body.dimensions.0.hypot(body.dimensions.1) / 2.0;
+    (0..FOOTPRINT_SAMPLES)
+        .map(move |sample| {
+            let angle = sample as f32 * std::f32::consts::TAU / FOOTPRINT_SAMPLES as f32;
+            get_floor_height(floor, x + radius * angle.cos(), y + radius * angle.sin())
+        })
+        .chain(std::iter::once(get_floor_height(floor, x, y)))
+}
+
+/// Moves a generated body to a random spot where all of it, not only its centre, stays within the
+/// arena margin.
+fn place_randomly(generated: &mut body::Body, rng: &mut impl Rng) {
+    let position_max = (ARENA_WIDTH / 2.0) - ARENA_MARGIN - body::get_bounding_radius(generated);
+    generated.translation.0 = rng.gen_range(-position_max..position_max);
+    generated.translation.1 = rng.gen_range(-position_max..position_max);
+}
+
+/// Lowers a body generated for a flat floor onto the terrain. It is sunk to the lowest point under
+/// its footprint so that none of it floats above a slope.
+fn place_on_floor(body: &mut body::Body, floor: &FloorConfig) {
+    let lowest = get_footprint_floor_heights(body, floor).fold(f32::INFINITY, f32::min);
+    body.translation.2 += lowest;
+}
+
+/// Props are dynamic, so rather than sinking them into the terrain they are dropped onto it from
+/// above the highest point under their footprint.
+fn place_above_floor(body: &mut body::Body, floor: &FloorConfig) {
+    let highest = get_footprint_floor_heights(body, floor).fold(f32::NEG_INFINITY, f32::max);
+    body.translation.2 += highest;
+}
+
+fn seed_obstacle_in_open_space(
+    bodies: &mut Vec<body::Body>,
+    floor: &FloorConfig,
+    destructible_ratio: f64,
+    rng: &mut impl Rng,
+) {
+    let mut obstacle = create_random_obstacle(destructible_ratio, rng);
+    place_on_floor(&mut obstacle, floor);
 
     if !body::overlaps_existing_bodies(&obstacle, bodies) {
         bodies.push(obstacle);
     } else {
-        seed_obstacle_in_open_space(bodies);
+        seed_obstacle_in_open_space(bodies, floor, destructible_ratio, rng);
     }
 }
 
-fn seed_obstacles(bodies: &mut Vec<body::Body>) {
+fn seed_obstacles(
+    bodies: &mut Vec<body::Body>,
+    floor: &FloorConfig,
+    destructible_ratio: f64,
+    rng: &mut impl Rng,
+) {
     for _ in 0..MAX_OBSTACLES {
-        seed_obstacle_in_open_space(bodies);
+        seed_obstacle_in_open_space(bodies, floor, destructible_ratio, rng);
     }
 }
 
-fn create_random_obstacle() -> body::Body {
-    let mut rng = rand::thread_rng();
-    let margin = 25.0;
-    let position_max = (ARENA_WIDTH / 2.0) - margin;
-    let position_min = -position_max;
-    let position_x = rng.gen_range(position_min..position_max);
-    let position_y = rng.gen_range(position_min..position_max);
+fn create_random_obstacle(destructible_ratio: f64, rng: &mut impl Rng) -> body::Body {
+    if rng.gen_bool(RAMP_PROBABILITY) {
+        return create_random_ramp(rng);
+    }
+    if rng.gen_bool(PILLAR_PROBABILITY) {
+        return create_random_pillar(rng);
+    }
+
     let length = rng.gen_range(1.0..5.0);
     let width = rng.gen_range(1.0..5.0);
     let height = rng.gen_range(0.2..5.0);
-    body::Body {
-        id: Uuid::new_v4().to_string(),
+    let yaw = rng.gen_range(0.0..180.0);
+    // when configured, some of the crates can be shot apart so that cover erodes over a long match
+    let destructible = rng.gen_bool(destructible_ratio.clamp(0.0, 1.0));
+    let mut obstacle = body::Body {
+        id: get_random_id(rng),
+        team_id: None,
+        owner_id: None,
+        translation: (0.0, 0.0, height / 2.0),
+        rotation: EulerDegrees::from_yaw(yaw),
+        linvel: (0.0, 0.0, 0.0),
+        angvel: (0.0, 0.0, 0.0),
+        dimensions: (length, width, height),
+        mass: 100.0,
+        class: body::BodyClass::Obstacle,
+        hp: if destructible { DESTRUCTIBLE_HP } else { 0 },
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: None,
+        destructible,
+    };
+    place_randomly(&mut obstacle, rng);
+    obstacle
+}
+
+/// A ramp is a wedge resting on the floor. The slope stays below what the character controller
+/// can climb.
+fn create_random_ramp(rng: &mut impl Rng) -> body::Body {
+    let length = rng.gen_range(4.0..8.0);
+    let width = rng.gen_range(2.0..4.0);
+    let slope: f32 = rng.gen_range(10.0..30.0);
+    let yaw = rng.gen_range(0.0..360.0);
+    let height = length * slope.to_radians().tan();
+    let mut ramp = body::Body {
+        id: get_random_id(rng),
         team_id: None,
         owner_id: None,
-        translation: (position_x, position_y, height / 2.0),
-        rotation: (0.0, 0.0, 0.0),
+        translation: (0.0, 0.0, height / 2.0),
+        rotation: EulerDegrees::from_yaw(yaw),
         linvel: (0.0, 0.0, 0.0),
         angvel: (0.0, 0.0, 0.0),
         dimensions: (length, width, height),
         mass: 100.0,
         class: body::BodyClass::Obstacle,
         hp: 0,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: Some(ShapeDescriptor::Wedge {
+            half_extents: (length / 2.0, width / 2.0, height / 2.0),
+        }),
+        destructible: false,
+    };
+    place_randomly(&mut ramp, rng);
+    ramp
+}
+
+fn create_random_pillar(rng: &mut impl Rng) -> body::Body {
+    let radius = rng.gen_range(0.5..2.0);
+    let height = rng.gen_range(2.0..6.0);
+    let mut pillar = body::Body {
+        id: get_random_id(rng),
+        team_id: None,
+        owner_id: None,
+        translation: (0.0, 0.0, height / 2.0),
+        rotation: EulerDegrees::default(),
+        linvel: (0.0, 0.0, 0.0),
+        angvel: (0.0, 0.0, 0.0),
+        dimensions: (radius * 2.0, radius * 2.0, height),
+        mass: 100.0,
+        class: body::BodyClass::Obstacle,
+        hp: 0,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: Some(ShapeDescriptor::Cylinder {
+            half_height: height / 2.0,
+            radius,
+        }),
+        destructible: false,
+    };
+    place_randomly(&mut pillar, rng);
+    pillar
+}
+
+fn seed_prop_in_open_space(bodies: &mut Vec<body::Body>, floor: &FloorConfig, rng: &mut impl Rng) {
+    let mut prop = create_random_prop(rng);
+    place_above_floor(&mut prop, floor);
+
+    if !body::overlaps_existing_bodies(&prop, bodies) {
+        bodies.push(prop);
+    } else {
+        seed_prop_in_open_space(bodies, floor, rng);
+    }
+}
+
+fn seed_props(bodies: &mut Vec<body::Body>, floor: &FloorConfig, rng: &mut impl Rng) {
+    for _ in 0..MAX_PROPS {
+        seed_prop_in_open_space(bodies, floor, rng);
+    }
+}
+
+/// Creates a crate or a barrel.
+fn create_random_prop(rng: &mut impl Rng) -> body::Body {
+    let (dimensions, mass, shape) = if rng.gen_bool(0.5) {
+        ((CRATE_SIZE, CRATE_SIZE, CRATE_SIZE), CRATE_MASS, None)
+    } else {
+        (
+            (BARREL_RADIUS * 2.0, BARREL_RADIUS * 2.0, BARREL_HEIGHT),
+            BARREL_MASS,
+            Some(ShapeDescriptor::Cylinder {
+                half_height: BARREL_HEIGHT / 2.0,
+                radius: BARREL_RADIUS,
+            }),
+        )
+    };
+    let mut prop = body::Body {
+        id: get_random_id(rng),
+        team_id: None,
+        owner_id: None,
+        translation: (0.0, 0.0, dimensions.2 / 2.0),
+        rotation: EulerDegrees::from_yaw(rng.gen_range(0.0..360.0)),
+        linvel: (0.0, 0.0, 0.0),
+        angvel: (0.0, 0.0, 0.0),
+        dimensions,
+        mass,
+        class: body::BodyClass::Prop,
+        hp: 0,
+        pickup: None,
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape,
+        destructible: false,
+    };
+    place_randomly(&mut prop, rng);
+    prop
+}
+
+fn seed_pickup_in_open_space(
+    bodies: &mut Vec<body::Body>,
+    floor: &FloorConfig,
+    rng: &mut impl Rng,
+) {
+    let mut pickup = create_random_pickup(rng);
+    let (x, y, _z) = pickup.translation;
+    pickup.translation.2 += get_floor_height(floor, x, y);
+
+    if !body::overlaps_existing_bodies(&pickup, bodies) {
+        bodies.push(pickup);
+    } else {
+        seed_pickup_in_open_space(bodies, floor, rng);
     }
 }
 
-pub fn get_initial_bodies() -> Vec<body::Body> {
+fn seed_pickups(bodies: &mut Vec<body::Body>, floor: &FloorConfig, rng: &mut impl Rng) {
+    for _ in 0..MAX_PICKUPS {
+        seed_pickup_in_open_space(bodies, floor, rng);
+    }
+}
+
+fn create_random_pickup(rng: &mut impl Rng) -> body::Body {
+    let effect = match rng.gen_range(0..5) {
+        0 => pickup::PickupEffect::Health { amount: 5 },
+        1 => pickup::PickupEffect::Ammo { amount: 20 },
+        2 => pickup::PickupEffect::Speed {
+            multiplier: 1.5,
+            duration_seconds: 10.0,
+        },
+        3 => pickup::PickupEffect::Damage {
+            multiplier: 2.0,
+            duration_seconds: 10.0,
+        },
+        _ => pickup::PickupEffect::Shield {
+            amount: 5.0,
+            duration_seconds: 15.0,
+        },
+    };
+    let mut pickup = body::Body {
+        id: get_random_id(rng),
+        team_id: None,
+        owner_id: None,
+        translation: (0.0, 0.0, PICKUP_SIZE / 2.0),
+        rotation: EulerDegrees::default(),
+        linvel: (0.0, 0.0, 0.0),
+        angvel: (0.0, 0.0, 0.0),
+        dimensions: (PICKUP_SIZE, PICKUP_SIZE, PICKUP_SIZE),
+        mass: 0.0,
+        class: body::BodyClass::Pickup,
+        hp: 0,
+        pickup: Some(pickup::Pickup {
+            effect,
+            respawn_seconds: 30.0,
+        }),
+        buffs: Vec::new(),
+        ammo: None,
+        movement: None,
+        aim: None,
+        shape: None,
+        destructible: false,
+    };
+    place_randomly(&mut pickup, rng);
+    pickup
+}
+
+/// Picks a random point on the ring players start on, for a body of the given height.
+pub fn get_spawn_candidate(rng: &mut impl Rng, height: f32) -> (f32, f32, f32) {
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+
+    (
+        SPAWN_RING_RADIUS * angle.cos(),
+        SPAWN_RING_RADIUS * angle.sin(),
+        height / 2.0,
+    )
+}
+
+/// Generates the arena's bodies. `destructible_ratio` is the share of the crates that can be shot
+/// apart.
+pub fn get_initial_bodies(
+    floor: &FloorConfig,
+    destructible_ratio: f64,
+    rng: &mut impl Rng,
+) -> Vec<body::Body> {
     let mut initial_bodies: Vec<body::Body> = Vec::new();
-    let floor = create_floor();
-    initial_bodies.push(floor);
-    seed_obstacles(&mut initial_bodies);
+    // everything is placed on the floor already, so it is only checked against each other
+    seed_obstacles(&mut initial_bodies, floor, destructible_ratio, rng);
+    seed_props(&mut initial_bodies, floor, rng);
+    seed_pickups(&mut initial_bodies, floor, rng);
+    initial_bodies.insert(0, create_floor(floor));
     initial_bodies
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    // a whole arena takes a while to generate in a debug build, so each property only sees a few
+    const CASES: u32 = 4;
+
+    fn generate(floor: &FloorConfig, seed: u64) -> Vec<body::Body> {
+        get_initial_bodies(floor, 0.0, &mut ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    fn get_terrain_floor(seed: u64) -> FloorConfig {
+        FloorConfig::Heightfield(TerrainConfig {
+            seed,
+            resolution: 32,
+            ..TerrainConfig::default()
+        })
+    }
+
+    fn get_bottom(body: &body::Body) -> f32 {
+        body.translation.2 - body.dimensions.2 / 2.0
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+        #[test]
+        fn generated_bodies_never_overlap(seed in any::<u64>()) {
+            let bodies = generate(&FloorConfig::Flat, seed);
+
+            // like generation itself, this leaves out the floor that everything rests on
+            for index in 2..bodies.len() {
+                prop_assert!(
+                    !body::overlaps_existing_bodies(&bodies[index], &bodies[1..index]),
+                    "{} overlaps an earlier body",
+                    bodies[index].id
+                );
+            }
+        }
+
+        #[test]
+        fn generated_bodies_stay_within_the_arena_margin(seed in any::<u64>()) {
+            let limit = ARENA_WIDTH / 2.0 - ARENA_MARGIN;
+
+            for body in generate(&FloorConfig::Flat, seed).iter().skip(1) {
+                let (x, y, _z) = body.translation;
+                let radius = body::get_bounding_radius(body);
+                prop_assert!(
+                    x.abs() + radius <= limit && y.abs() + radius <= limit,
+                    "{} reaches past the margin from {:?}", body.id, body.translation
+                );
+            }
+        }
+
+        #[test]
+        fn generated_bodies_sit_on_a_flat_floor(seed in any::<u64>()) {
+            for body in generate(&FloorConfig::Flat, seed).iter().skip(1) {
+                prop_assert!(get_bottom(body).abs() < 1e-4, "{} is at {:?}", body.id, body.translation);
+            }
+        }
+
+        #[test]
+        fn obstacles_never_float_above_terrain(seed in any::<u64>()) {
+            let floor = get_terrain_floor(seed);
+
+            for body in generate(&floor, seed).iter().skip(1) {
+                let (x, y, _z) = body.translation;
+                let floor_height = get_floor_height(&floor, x, y);
+                match body.class {
+                    // sunk into slopes, so at least the middle of the obstacle meets the ground
+                    body::BodyClass::Obstacle => prop_assert!(get_bottom(body) <= floor_height + 1e-4),
+                    // dropped from above, so none of the prop starts inside the ground
+                    body::BodyClass::Prop => prop_assert!(get_bottom(body) >= floor_height - 1e-4),
+                    _ => prop_assert!((get_bottom(body) - floor_height).abs() < 1e-4),
+                }
+            }
+        }
+
+        #[test]
+        fn the_same_seed_generates_the_same_arena(seed in any::<u64>()) {
+            let arena = serde_json::to_string(&generate(&FloorConfig::Flat, seed)).unwrap();
+
+            prop_assert_eq!(&arena, &serde_json::to_string(&generate(&FloorConfig::Flat, seed)).unwrap());
+            prop_assert_ne!(&arena, &serde_json::to_string(&generate(&FloorConfig::Flat, seed.wrapping_add(1))).unwrap());
+        }
+    }
+}
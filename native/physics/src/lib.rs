@@ -2,20 +2,37 @@ extern crate rustler;
 
 use rapier3d::prelude::*;
 use serde::Serialize;
-use serde_json;
 use std::collections::HashSet;
 use std::io::{BufWriter, Stdout, Write};
 use std::thread;
 use std::time::{Duration, Instant};
 
-mod body;
-mod game;
+pub mod body;
+mod bot;
+mod checkpoint;
+pub mod config;
+mod events;
+pub mod game;
 mod init;
+mod metrics;
+mod mode;
+mod movement;
+mod nav;
 mod physics_world;
-mod user_input;
+mod pickup;
+mod replay;
+mod rotation;
+mod rules;
+mod shape;
+mod simulate;
+mod terrain;
+pub mod user_input;
 mod util;
 
-fn write_update_to_stdout<A: Serialize>(writer: &mut BufWriter<Stdout>, msg: A) {
+#[cfg(test)]
+mod tests;
+
+fn write_update_to_stdout<A: Serialize>(writer: &mut impl Write, msg: A) {
     match serde_json::to_writer(&mut *writer, &msg) {
         Ok(_) => {
             writer.write_all(b"\n").expect("Failed to write newline.");
@@ -27,19 +44,64 @@ fn write_update_to_stdout<A: Serialize>(writer: &mut BufWriter<Stdout>, msg: A)
     };
 }
 
-fn write_body_updates(
+/// Writes the current state of every updated body as one JSON line.
+pub fn write_body_updates(
     updated_handles: &HashSet<RigidBodyHandle>,
     game_state: &game::Game,
-    writer: &mut BufWriter<Stdout>,
+    writer: &mut impl Write,
 ) {
     let next_bodies: Vec<body::Body> = updated_handles
         .iter()
-        .filter_map(|handle| game::get_body_from_handle(&game_state, handle))
+        .filter_map(|handle| game::get_body_from_handle(game_state, handle))
         .collect();
 
     write_update_to_stdout(writer, &next_bodies);
 }
 
+fn write_events(events: &[events::GameEvent], writer: &mut BufWriter<Stdout>) {
+    for event in events {
+        write_update_to_stdout(writer, event);
+    }
+}
+
+fn write_checkpoints(
+    game_state: &mut game::Game,
+    game_mode: &dyn mode::GameMode,
+    writer: &mut BufWriter<Stdout>,
+) {
+    for path in game::take_snapshot_requests(game_state) {
+        match checkpoint::save(&path, game_state, game_mode) {
+            Ok(()) => write_update_to_stdout(writer, events::GameEvent::SnapshotSaved { path }),
+            Err(err) => eprintln!("Failed to save snapshot to {}: {}", path, err),
+        }
+    }
+}
+
+/// Metrics go to stderr, as the Elixir server reads nothing but the protocol from stdout.
+fn write_metrics(
+    tick_metrics: &mut metrics::Metrics,
+    timings: &metrics::TickTimings,
+    game_state: &game::Game,
+    interval_ticks: Option<u64>,
+) {
+    let interval_ticks = match interval_ticks {
+        Some(interval_ticks) => interval_ticks.max(1),
+        None => return,
+    };
+    let budget = Duration::from_secs_f32(game::get_tick_ms(game_state) / 1000.0);
+    metrics::record_tick(tick_metrics, timings, budget);
+
+    let tick = game::get_tick(game_state);
+    if tick % interval_ticks == 0 {
+        let report =
+            metrics::take_report(tick_metrics, tick, game::count_bodies_by_class(game_state));
+        match serde_json::to_string(&report) {
+            Ok(line) => eprintln!("{}", line),
+            Err(err) => eprintln!("Failed to serialize metrics: {}", err),
+        }
+    }
+}
+
 fn sleep_for_remaining_time(loop_start: Instant, integration_dt_ms: f32) {
     let elapsed_ms = loop_start.elapsed().as_millis() as f32;
     if elapsed_ms < integration_dt_ms {
@@ -48,40 +110,140 @@ fn sleep_for_remaining_time(loop_start: Instant, integration_dt_ms: f32) {
     }
 }
 
+fn run_replay(path: &str) {
+    match replay::verify(path) {
+        Ok(ticks) => println!("Replay matched the recording for {} ticks", ticks),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_simulation() {
+    let config = config::from_args();
+    let seed = config::seed_from_args();
+    let ticks = config::get_arg("--ticks")
+        .map(|ticks| ticks.parse().expect("Invalid --ticks"))
+        .unwrap_or(simulate::DEFAULT_TICKS);
+    let script = config::get_arg("--script")
+        .map(|path| simulate::load_script(&path).expect("Failed to load script"))
+        .unwrap_or_default();
+
+    let summary = simulate::run(&config, seed, ticks, script);
+    println!(
+        "{}",
+        serde_json::to_string(&summary).expect("Failed to serialize summary")
+    );
+}
+
 pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("replay") => {
+            run_replay(args.get(2).expect("Missing path after replay"));
+            return;
+        }
+        Some("simulate") => {
+            run_simulation();
+            return;
+        }
+        _ => {}
+    }
+
     let mut writer = std::io::BufWriter::new(std::io::stdout());
     let stdin_channel = user_input::spawn_input_channel().expect("Failed to spawn stdin channel");
-    let mut game_state = game::init();
+    let config = config::from_args();
+    let seed = config::seed_from_args();
+    let restore_path = config::get_arg("--restore");
+    let (mut game_state, mut game_mode) = match &restore_path {
+        Some(path) => checkpoint::load(path, &config).expect("Failed to restore checkpoint"),
+        None => mode::start_match(&config, seed),
+    };
+    let mut recorder = config::get_arg("--record").map(|path| {
+        replay::start_recording(&path, seed, &config, restore_path.clone())
+            .expect("Failed to start recording")
+    });
 
     let mut updated_handles: HashSet<RigidBodyHandle> = HashSet::new();
     let initial_world_handles = game::get_handles(&game_state);
     updated_handles.extend(initial_world_handles);
 
-    let mut is_won: bool = false;
+    let mut result: Option<mode::MatchResult> = None;
     let integration_dt_ms = game::get_tick_ms(&game_state);
+    let mut tick_metrics = metrics::Metrics::default();
 
-    while !is_won {
+    while result.is_none() {
         let loop_start = Instant::now();
-
-        // 1. handle user input
-        let user_updated_handles = user_input::handle_user_input(&stdin_channel, &mut game_state);
+        let mut timings = metrics::TickTimings {
+            input_queue_depth: user_input::get_queue_depth(&stdin_channel),
+            ..metrics::TickTimings::default()
+        };
+
+        // 1. handle user input, then let the bots decide on theirs
+        let inputs = user_input::read_user_input(&stdin_channel);
+        timings.input_parsing = loop_start.elapsed();
+        let input_handling_start = Instant::now();
+        let user_updated_handles = user_input::apply_user_input(&inputs, &mut game_state);
         updated_handles.extend(user_updated_handles);
+        let bot_inputs = game::get_bot_inputs(&mut game_state);
+        let bot_updated_handles = user_input::apply_user_input(&bot_inputs, &mut game_state);
+        updated_handles.extend(bot_updated_handles);
+        timings.input_handling = input_handling_start.elapsed();
 
         // 2. step physics world and handle updates
+        let step_start = Instant::now();
         let physics_updated_handles = game::step(&mut game_state);
+        timings.step = step_start.elapsed();
+        timings.step_parts = game::get_step_timings(&game_state);
         updated_handles.extend(physics_updated_handles);
+        if let Some(recorder) = recorder.as_mut() {
+            if let Err(err) = replay::record_tick(recorder, &game_state, inputs) {
+                eprintln!("Failed to record tick: {}", err);
+            }
+        }
 
         // 3. write updated bodies to channel
+        let serialization_start = Instant::now();
         write_body_updates(&updated_handles, &game_state, &mut writer);
-
-        // 4. check win condition and write to channel if necessary
-        is_won = game::is_won(&game_state);
+        let mut serialization = serialization_start.elapsed();
+
+        // 4. let the game mode inspect this tick's events and write them to channel
+        let events = game::take_events(&mut game_state);
+        result = game_mode.update(&game_state, &events);
+        let serialization_start = Instant::now();
+        write_events(&events, &mut writer);
+        serialization += serialization_start.elapsed();
+        timings.serialization = serialization;
         updated_handles.clear();
 
-        // 5. handle leftover time
+        // 5. write any checkpoints asked for, now that the tick is complete
+        write_checkpoints(&mut game_state, &*game_mode, &mut writer);
+
+        // 6. collect this tick's timings and report them every so often
+        timings.total = loop_start.elapsed();
+        write_metrics(
+            &mut tick_metrics,
+            &timings,
+            &game_state,
+            config.metrics_interval_ticks,
+        );
+
+        // 7. handle leftover time
         sleep_for_remaining_time(loop_start, integration_dt_ms);
     }
 
+    if let Some(mode::MatchResult {
+        winning_team_id,
+        scores,
+    }) = result
+    {
+        let match_ended = events::GameEvent::MatchEnded {
+            winning_team_id,
+            scores,
+        };
+        write_update_to_stdout(&mut writer, match_ended);
+    }
     write_update_to_stdout(&mut writer, "game_won");
 }
 
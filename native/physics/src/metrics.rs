@@ -0,0 +1,179 @@
+use crate::body::BodyClass;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How long the parts of `game::step` took.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StepTimings {
+    // moving the players' character controllers
+    pub characters: Duration,
+    pub physics: Duration,
+    pub collisions: Duration,
+    pub stale_sweep: Duration,
+}
+
+/// Where one pass of the port loop spent its time, leaving out the sleep until the next tick.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TickTimings {
+    pub input_parsing: Duration,
+    // applying client inputs and letting the bots decide on theirs
+    pub input_handling: Duration,
+    pub step: Duration,
+    pub step_parts: StepTimings,
+    pub serialization: Duration,
+    pub total: Duration,
+    // input lines waiting when the tick started
+    pub input_queue_depth: usize,
+}
+
+/// Tick timings collected since the last report.
+#[derive(Default)]
+pub struct Metrics {
+    ticks: u64,
+    sums: TickTimings,
+    max_total: Duration,
+    ticks_over_budget: u64,
+    max_input_queue_depth: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename = "metrics")]
+pub struct Report {
+    pub tick: u64,
+    // the number of ticks the figures below cover
+    pub ticks: u64,
+    pub mean_ms: PhaseMillis,
+    pub max_tick_ms: f32,
+    pub ticks_over_budget: u64,
+    pub mean_input_queue_depth: f32,
+    pub max_input_queue_depth: usize,
+    pub bodies: BTreeMap<BodyClass, usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PhaseMillis {
+    pub input_parsing: f32,
+    pub input_handling: f32,
+    pub step: f32,
+    pub characters: f32,
+    pub physics: f32,
+    pub collisions: f32,
+    pub stale_sweep: f32,
+    pub serialization: f32,
+    pub total: f32,
+}
+
+/// Adds a tick to the running figures. Ticks that took longer than `budget` left no time to sleep
+/// and delayed the next one.
+pub fn record_tick(metrics: &mut Metrics, timings: &TickTimings, budget: Duration) {
+    let sums = &mut metrics.sums;
+    sums.input_parsing += timings.input_parsing;
+    sums.input_handling += timings.input_handling;
+    sums.step += timings.step;
+    sums.step_parts.characters += timings.step_parts.characters;
+    sums.step_parts.physics += timings.step_parts.physics;
+    sums.step_parts.collisions += timings.step_parts.collisions;
+    sums.step_parts.stale_sweep += timings.step_parts.stale_sweep;
+    sums.serialization += timings.serialization;
+    sums.total += timings.total;
+    sums.input_queue_depth += timings.input_queue_depth;
+
+    metrics.ticks += 1;
+    metrics.max_total = metrics.max_total.max(timings.total);
+    metrics.max_input_queue_depth = metrics.max_input_queue_depth.max(timings.input_queue_depth);
+    if timings.total > budget {
+        metrics.ticks_over_budget += 1;
+    }
+}
+
+/// Sums up the ticks recorded since the last report and starts collecting afresh.
+pub fn take_report(metrics: &mut Metrics, tick: u64, bodies: BTreeMap<BodyClass, usize>) -> Report {
+    let Metrics {
+        ticks,
+        sums,
+        max_total,
+        ticks_over_budget,
+        max_input_queue_depth,
+    } = std::mem::take(metrics);
+    let mean_ms = |sum: Duration| match ticks {
+        0 => 0.0,
+        ticks => to_millis(sum) / ticks as f32,
+    };
+
+    Report {
+        tick,
+        ticks,
+        mean_ms: PhaseMillis {
+            input_parsing: mean_ms(sums.input_parsing),
+            input_handling: mean_ms(sums.input_handling),
+            step: mean_ms(sums.step),
+            characters: mean_ms(sums.step_parts.characters),
+            physics: mean_ms(sums.step_parts.physics),
+            collisions: mean_ms(sums.step_parts.collisions),
+            stale_sweep: mean_ms(sums.step_parts.stale_sweep),
+            serialization: mean_ms(sums.serialization),
+            total: mean_ms(sums.total),
+        },
+        max_tick_ms: to_millis(max_total),
+        ticks_over_budget,
+        mean_input_queue_depth: match ticks {
+            0 => 0.0,
+            ticks => sums.input_queue_depth as f32 / ticks as f32,
+        },
+        max_input_queue_depth,
+        bodies,
+    }
+}
+
+fn to_millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick_taking(total_ms: u64, input_queue_depth: usize) -> TickTimings {
+        TickTimings {
+            step: Duration::from_millis(total_ms / 2),
+            total: Duration::from_millis(total_ms),
+            input_queue_depth,
+            ..TickTimings::default()
+        }
+    }
+
+    #[test]
+    fn reports_average_the_ticks_since_the_last_report() {
+        let mut metrics = Metrics::default();
+        let budget = Duration::from_millis(16);
+        record_tick(&mut metrics, &tick_taking(4, 1), budget);
+        record_tick(&mut metrics, &tick_taking(20, 5), budget);
+
+        let report = take_report(&mut metrics, 2, BTreeMap::new());
+
+        assert_eq!(report.ticks, 2);
+        assert!((report.mean_ms.total - 12.0).abs() < 1e-3);
+        assert!((report.mean_ms.step - 6.0).abs() < 1e-3);
+        assert!((report.max_tick_ms - 20.0).abs() < 1e-3);
+        assert_eq!(report.ticks_over_budget, 1);
+        assert!((report.mean_input_queue_depth - 3.0).abs() < 1e-3);
+        assert_eq!(report.max_input_queue_depth, 5);
+
+        let next_report = take_report(&mut metrics, 2, BTreeMap::new());
+        assert_eq!(next_report.ticks, 0);
+        assert_eq!(next_report.max_input_queue_depth, 0);
+    }
+
+    #[test]
+    fn reports_are_written_as_metrics_events() {
+        let bodies = BTreeMap::from([(BodyClass::Player, 2), (BodyClass::Bullet, 7)]);
+        let report = take_report(&mut Metrics::default(), 60, bodies);
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["event"], "metrics");
+        assert_eq!(json["tick"], 60);
+        assert_eq!(json["bodies"]["bullet"], 7);
+        assert_eq!(json["bodies"]["player"], 2);
+    }
+}
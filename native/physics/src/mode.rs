@@ -0,0 +1,330 @@
+use crate::config::Config;
+use crate::events::GameEvent;
+use crate::game;
+use rapier3d::prelude::ColliderHandle;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchResult {
+    pub winning_team_id: Option<String>,
+    pub scores: BTreeMap<String, i32>,
+}
+
+pub trait GameMode {
+    /// Called once after the arena has been built, before the first tick.
+    fn setup(&mut self, _game_state: &mut game::Game) {}
+
+    /// Called after every tick with the events it produced. Returns the result once the match is
+    /// decided.
+    fn update(&mut self, game_state: &game::Game, events: &[GameEvent]) -> Option<MatchResult>;
+
+    /// Serialises whatever the mode tracks between ticks, for checkpointing alongside the game.
+    fn snapshot(&self) -> Result<Vec<u8>, String> {
+        Ok(Vec::new())
+    }
+
+    /// Replaces the mode's state with a snapshot. A restored mode is not set up again, since
+    /// anything `setup` added to the arena is part of the game snapshot.
+    fn restore(&mut self, _bytes: &[u8]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModeConfig {
+    #[default]
+    LastTeamStanding,
+    TeamDeathmatch {
+        kills_to_win: i32,
+    },
+    Timed {
+        duration_seconds: f32,
+    },
+    KingOfTheHill {
+        center: (f32, f32, f32),
+        half_extents: (f32, f32, f32),
+        hold_seconds_to_win: f32,
+    },
+}
+
+/// Builds a fresh arena from a seed and sets the configured mode up in it.
+pub fn start_match(config: &Config, seed: u64) -> (game::Game, Box<dyn GameMode>) {
+    let mut game_state = game::init(config, seed);
+    let mut game_mode = build(&config.mode);
+    game_mode.setup(&mut game_state);
+    (game_state, game_mode)
+}
+
+pub fn build(config: &ModeConfig) -> Box<dyn GameMode> {
+    match *config {
+        ModeConfig::LastTeamStanding => Box::new(LastTeamStanding),
+        ModeConfig::TeamDeathmatch { kills_to_win } => Box::new(TeamDeathmatch {
+            kills_to_win,
+            kills_by_team: BTreeMap::new(),
+        }),
+        ModeConfig::Timed { duration_seconds } => Box::new(Timed {
+            duration_ms: duration_seconds * 1000.0,
+            kills_by_team: BTreeMap::new(),
+        }),
+        ModeConfig::KingOfTheHill {
+            center,
+            half_extents,
+            hold_seconds_to_win,
+        } => Box::new(KingOfTheHill {
+            center,
+            half_extents,
+            hold_ms_to_win: hold_seconds_to_win * 1000.0,
+            hill: None,
+            held_ms_by_team: BTreeMap::new(),
+        }),
+    }
+}
+
+pub struct LastTeamStanding;
+
+impl GameMode for LastTeamStanding {
+    fn update(&mut self, game_state: &game::Game, _events: &[GameEvent]) -> Option<MatchResult> {
+        let teams_alive = game::get_teams_alive(game_state);
+        if teams_alive.len() != 1 {
+            return None;
+        }
+
+        let winning_team_id = teams_alive.into_iter().next();
+        let scores = winning_team_id
+            .iter()
+            .map(|team_id| (team_id.clone(), 1))
+            .collect();
+
+        Some(MatchResult {
+            winning_team_id,
+            scores,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TeamDeathmatch {
+    kills_to_win: i32,
+    kills_by_team: BTreeMap<String, i32>,
+}
+
+impl GameMode for TeamDeathmatch {
+    fn update(&mut self, _game_state: &game::Game, events: &[GameEvent]) -> Option<MatchResult> {
+        count_kills(&mut self.kills_by_team, events);
+
+        self.kills_by_team
+            .iter()
+            .find(|(_team_id, kills)| **kills >= self.kills_to_win)
+            .map(|(team_id, _kills)| MatchResult {
+                winning_team_id: Some(team_id.clone()),
+                scores: self.kills_by_team.clone(),
+            })
+    }
+
+    fn snapshot(&self) -> Result<Vec<u8>, String> {
+        encode(self)
+    }
+
+    fn restore(&mut self, bytes: &[u8]) -> Result<(), String> {
+        *self = decode(bytes)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Timed {
+    duration_ms: f32,
+    kills_by_team: BTreeMap<String, i32>,
+}
+
+impl GameMode for Timed {
+    fn update(&mut self, game_state: &game::Game, events: &[GameEvent]) -> Option<MatchResult> {
+        count_kills(&mut self.kills_by_team, events);
+
+        if game::get_elapsed_ms(game_state) < self.duration_ms {
+            return None;
+        }
+
+        Some(MatchResult {
+            winning_team_id: get_leader(&self.kills_by_team),
+            scores: self.kills_by_team.clone(),
+        })
+    }
+
+    fn snapshot(&self) -> Result<Vec<u8>, String> {
+        encode(self)
+    }
+
+    fn restore(&mut self, bytes: &[u8]) -> Result<(), String> {
+        *self = decode(bytes)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct KingOfTheHill {
+    center: (f32, f32, f32),
+    half_extents: (f32, f32, f32),
+    hold_ms_to_win: f32,
+    hill: Option<ColliderHandle>,
+    held_ms_by_team: BTreeMap<String, f32>,
+}
+
+impl GameMode for KingOfTheHill {
+    fn setup(&mut self, game_state: &mut game::Game) {
+        self.hill = Some(game::add_sensor_region(
+            game_state,
+            self.center,
+            self.half_extents,
+        ));
+    }
+
+    fn update(&mut self, game_state: &game::Game, _events: &[GameEvent]) -> Option<MatchResult> {
+        let hill = self.hill?;
+        let teams_on_hill = game::get_teams_in_region(game_state, hill);
+
+        // the hill only scores while it is uncontested
+        if teams_on_hill.len() == 1 {
+            if let Some(team_id) = teams_on_hill.into_iter().next() {
+                let held_ms = self.held_ms_by_team.entry(team_id).or_insert(0.0);
+                *held_ms += game::get_tick_ms(game_state);
+            }
+        }
+
+        let scores: BTreeMap<String, i32> = self
+            .held_ms_by_team
+            .iter()
+            .map(|(team_id, held_ms)| (team_id.clone(), (held_ms / 1000.0) as i32))
+            .collect();
+
+        self.held_ms_by_team
+            .iter()
+            .find(|(_team_id, held_ms)| **held_ms >= self.hold_ms_to_win)
+            .map(|(team_id, _held_ms)| MatchResult {
+                winning_team_id: Some(team_id.clone()),
+                scores,
+            })
+    }
+
+    fn snapshot(&self) -> Result<Vec<u8>, String> {
+        encode(self)
+    }
+
+    fn restore(&mut self, bytes: &[u8]) -> Result<(), String> {
+        *self = decode(bytes)?;
+        Ok(())
+    }
+}
+
+fn count_kills(kills_by_team: &mut BTreeMap<String, i32>, events: &[GameEvent]) {
+    for event in events {
+        if let GameEvent::Eliminated {
+            team_id,
+            attacker_team_id: Some(attacker_team_id),
+            ..
+        } = event
+        {
+            if team_id.as_ref() != Some(attacker_team_id) {
+                *kills_by_team.entry(attacker_team_id.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+fn get_leader(scores: &BTreeMap<String, i32>) -> Option<String> {
+    let top_score = scores.values().max()?;
+    let mut leaders = scores
+        .iter()
+        .filter(|(_team_id, score)| *score == top_score);
+
+    match (leaders.next(), leaders.next()) {
+        (Some((team_id, _score)), None) => Some(team_id.clone()),
+        _ => None,
+    }
+}
+
+fn encode<T: Serialize>(state: &T) -> Result<Vec<u8>, String> {
+    rmp_serde::to_vec_named(state).map_err(|err| err.to_string())
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    rmp_serde::from_slice(bytes).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eliminated(team_id: &str, attacker_team_id: &str) -> GameEvent {
+        GameEvent::Eliminated {
+            id: format!("{}-player", team_id),
+            team_id: Some(team_id.to_string()),
+            attacker_id: Some(format!("{}-player", attacker_team_id)),
+            attacker_team_id: Some(attacker_team_id.to_string()),
+        }
+    }
+
+    // a one second match, played out in an empty arena
+    fn timed_match() -> (game::Game, Box<dyn GameMode>) {
+        let config = Config {
+            mode: ModeConfig::Timed {
+                duration_seconds: 1.0,
+            },
+            ..Config::default()
+        };
+        (game::init_empty(&config, 0), build(&config.mode))
+    }
+
+    fn run_out_the_clock(game_state: &mut game::Game) {
+        while game::get_elapsed_ms(game_state) < 1000.0 {
+            game::step(game_state);
+        }
+    }
+
+    #[test]
+    fn a_timed_match_is_won_by_the_team_with_the_most_kills_when_time_runs_out() {
+        let (mut game_state, mut game_mode) = timed_match();
+        let kills = [eliminated("blue", "red"), eliminated("red", "blue")];
+        assert_eq!(game_mode.update(&game_state, &kills), None);
+        assert_eq!(
+            game_mode.update(&game_state, &[eliminated("blue", "red")]),
+            None
+        );
+
+        run_out_the_clock(&mut game_state);
+        let result = game_mode.update(&game_state, &[]).unwrap();
+
+        assert_eq!(result.winning_team_id.as_deref(), Some("red"));
+        assert_eq!(
+            result.scores,
+            BTreeMap::from([("blue".to_string(), 1), ("red".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn a_timed_match_tied_when_time_runs_out_has_no_winner() {
+        let (mut game_state, mut game_mode) = timed_match();
+        let kills = [eliminated("blue", "red"), eliminated("red", "blue")];
+        game_mode.update(&game_state, &kills);
+
+        run_out_the_clock(&mut game_state);
+        let result = game_mode.update(&game_state, &[]).unwrap();
+
+        assert_eq!(result.winning_team_id, None);
+        assert_eq!(
+            result.scores,
+            BTreeMap::from([("blue".to_string(), 1), ("red".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn team_kills_do_not_score() {
+        let mut kills_by_team = BTreeMap::new();
+        count_kills(&mut kills_by_team, &[eliminated("red", "red")]);
+
+        assert!(kills_by_team.is_empty());
+    }
+}
@@ -0,0 +1,438 @@
+use rapier3d::control::{
+    CharacterAutostep, CharacterLength, EffectiveCharacterMovement, KinematicCharacterController,
+};
+use rapier3d::na::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    // metres per second
+    pub max_speed: f32,
+    // metres per second squared, while grounded
+    pub acceleration: f32,
+    // fraction of `acceleration` available while airborne
+    pub air_control: f32,
+    pub max_slope_climb_degrees: f32,
+    pub min_slope_slide_degrees: f32,
+    pub step_height: f32,
+    pub step_min_width: f32,
+    pub snap_to_ground: f32,
+    // vertical speed given by a jump, in metres per second
+    pub jump_speed: f32,
+    // how long after walking off a ledge a jump is still allowed
+    pub coyote_time_ms: f32,
+    // how long a jump pressed in mid-air is remembered and performed on landing
+    pub jump_buffer_ms: f32,
+    pub sprint_multiplier: f32,
+    pub max_stamina: f32,
+    pub stamina_drain_per_second: f32,
+    pub stamina_regen_per_second: f32,
+    // total capsule height while crouching, in metres
+    pub crouch_height: f32,
+    pub crouch_speed_multiplier: f32,
+    // horizontal speed a dash starts with, in metres per second
+    pub dash_speed: f32,
+    pub dash_cooldown_ms: f32,
+    // how far players may look up or down from the horizon
+    pub max_aim_pitch_degrees: f32,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        MovementConfig {
+            max_speed: 8.0,
+            acceleration: 60.0,
+            air_control: 0.3,
+            max_slope_climb_degrees: 45.0,
+            min_slope_slide_degrees: 30.0,
+            step_height: 0.4,
+            step_min_width: 0.2,
+            snap_to_ground: 0.2,
+            jump_speed: 10.0,
+            coyote_time_ms: 100.0,
+            jump_buffer_ms: 100.0,
+            sprint_multiplier: 1.6,
+            max_stamina: 100.0,
+            stamina_drain_per_second: 25.0,
+            stamina_regen_per_second: 15.0,
+            crouch_height: 1.2,
+            crouch_speed_multiplier: 0.5,
+            dash_speed: 20.0,
+            dash_cooldown_ms: 1000.0,
+            max_aim_pitch_degrees: 85.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CharacterState {
+    pub velocity: Vector3<f32>,
+    // desired direction in the xy plane; its length (capped at 1) is the fraction of max speed
+    pub move_direction: (f32, f32),
+    pub grounded: bool,
+    pub airborne_ticks: u32,
+    // set once a jump has been performed, until the character lands again
+    pub jumped: bool,
+    // ticks since a not yet performed jump was requested
+    pub buffered_jump_ticks: Option<u32>,
+    pub wants_sprint: bool,
+    pub sprinting: bool,
+    pub stamina: f32,
+    pub wants_crouch: bool,
+    pub crouching: bool,
+    pub standing_height: f32,
+    pub dash_requested: bool,
+    pub dash_cooldown_ticks: u32,
+}
+
+/// The movement state reported for players in body updates.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct MovementStatus {
+    pub sprinting: bool,
+    pub crouching: bool,
+    pub stamina: f32,
+    pub dash_cooldown_seconds: f32,
+}
+
+/// Where a player is looking, in degrees. The physics body only ever turns to the yaw; the pitch
+/// is kept separately so the capsule stays upright.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Aim {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+pub fn get_aim(config: &MovementConfig, yaw: f32, pitch: f32) -> Aim {
+    let max_pitch = config.max_aim_pitch_degrees.clamp(0.0, 90.0);
+    Aim {
+        // matches the [0, 360) range body rotations are reported in
+        yaw: yaw.rem_euclid(360.0),
+        pitch: pitch.clamp(-max_pitch, max_pitch),
+    }
+}
+
+pub fn init_character(
+    config: &MovementConfig,
+    linvel: (f32, f32, f32),
+    standing_height: f32,
+) -> CharacterState {
+    CharacterState {
+        velocity: Vector3::new(linvel.0, linvel.1, linvel.2),
+        move_direction: (0.0, 0.0),
+        grounded: false,
+        airborne_ticks: 0,
+        jumped: false,
+        buffered_jump_ticks: None,
+        wants_sprint: false,
+        sprinting: false,
+        stamina: config.max_stamina,
+        wants_crouch: false,
+        crouching: false,
+        standing_height,
+        dash_requested: false,
+        dash_cooldown_ticks: 0,
+    }
+}
+
+pub fn get_status(character: &CharacterState, dt: f32) -> MovementStatus {
+    MovementStatus {
+        sprinting: character.sprinting,
+        crouching: character.crouching,
+        stamina: character.stamina,
+        dash_cooldown_seconds: character.dash_cooldown_ticks as f32 * dt,
+    }
+}
+
+/// Returns the capsule height the character should switch to, if its crouch request differs from
+/// its current stance.
+pub fn get_stance_change(config: &MovementConfig, character: &CharacterState) -> Option<f32> {
+    match (character.wants_crouch, character.crouching) {
+        (true, false) => Some(config.crouch_height.min(character.standing_height)),
+        (false, true) => Some(character.standing_height),
+        _ => None,
+    }
+}
+
+const SPRINT_RESUME_STAMINA_FRACTION: f32 = 0.25;
+
+fn update_sprint(
+    config: &MovementConfig,
+    character: &mut CharacterState,
+    is_moving: bool,
+    dt: f32,
+) {
+    // an exhausted player has to recover some stamina before sprinting again, otherwise the sprint
+    // would flicker on and off every tick while stamina hovers around zero
+    let has_stamina = if character.sprinting {
+        character.stamina > 0.0
+    } else {
+        character.stamina >= config.max_stamina * SPRINT_RESUME_STAMINA_FRACTION
+    };
+    character.sprinting =
+        character.wants_sprint && is_moving && !character.crouching && has_stamina;
+
+    character.stamina = if character.sprinting {
+        (character.stamina - config.stamina_drain_per_second * dt).max(0.0)
+    } else {
+        (character.stamina + config.stamina_regen_per_second * dt).min(config.max_stamina)
+    };
+}
+
+fn try_dash(
+    config: &MovementConfig,
+    character: &mut CharacterState,
+    direction: Vector2<f32>,
+    horizontal: Vector2<f32>,
+    dt: f32,
+) -> Vector2<f32> {
+    character.dash_cooldown_ticks = character.dash_cooldown_ticks.saturating_sub(1);
+    if !std::mem::take(&mut character.dash_requested) || character.dash_cooldown_ticks > 0 {
+        return horizontal;
+    }
+
+    match direction.try_normalize(f32::EPSILON) {
+        Some(dash_direction) => {
+            character.dash_cooldown_ticks = (config.dash_cooldown_ms / (dt * 1000.0)).ceil() as u32;
+            dash_direction * config.dash_speed
+        }
+        None => horizontal,
+    }
+}
+
+pub fn request_jump(character: &mut CharacterState) {
+    character.buffered_jump_ticks = Some(0);
+}
+
+fn try_jump(config: &MovementConfig, character: &mut CharacterState, dt: f32) {
+    let ticks_requested = match character.buffered_jump_ticks {
+        Some(ticks) => ticks,
+        None => return,
+    };
+
+    let airborne_ms = character.airborne_ticks as f32 * dt * 1000.0;
+    if !character.jumped && airborne_ms <= config.coyote_time_ms {
+        character.velocity.z = config.jump_speed;
+        character.jumped = true;
+        character.buffered_jump_ticks = None;
+    } else if (ticks_requested + 1) as f32 * dt * 1000.0 > config.jump_buffer_ms {
+        character.buffered_jump_ticks = None;
+    } else {
+        character.buffered_jump_ticks = Some(ticks_requested + 1);
+    }
+}
+
+pub fn build_controller(config: &MovementConfig) -> KinematicCharacterController {
+    KinematicCharacterController {
+        up: Vector3::z_axis(),
+        autostep: Some(CharacterAutostep {
+            max_height: CharacterLength::Absolute(config.step_height),
+            min_width: CharacterLength::Absolute(config.step_min_width),
+            include_dynamic_bodies: false,
+        }),
+        max_slope_climb_angle: config.max_slope_climb_degrees.to_radians(),
+        min_slope_slide_angle: config.min_slope_slide_degrees.to_radians(),
+        snap_to_ground: Some(CharacterLength::Absolute(config.snap_to_ground)),
+        ..KinematicCharacterController::default()
+    }
+}
+
+/// Advances the character's velocity by one tick of steering, jumping and gravity, returning the
+/// velocity the character wants to move with this tick.
+pub fn step_velocity(
+    config: &MovementConfig,
+    character: &mut CharacterState,
+    facing: Vector2<f32>,
+    speed_multiplier: f32,
+    gravity: f32,
+    dt: f32,
+) -> Vector3<f32> {
+    let direction = Vector2::new(character.move_direction.0, character.move_direction.1);
+    let direction = if direction.norm() > 1.0 {
+        direction.normalize()
+    } else {
+        direction
+    };
+
+    let is_moving = direction.norm() > 0.0;
+    update_sprint(config, character, is_moving, dt);
+    let stance_multiplier = if character.crouching {
+        config.crouch_speed_multiplier
+    } else if character.sprinting {
+        config.sprint_multiplier
+    } else {
+        1.0
+    };
+    let target = direction * config.max_speed * speed_multiplier * stance_multiplier;
+
+    let acceleration = if character.grounded {
+        config.acceleration
+    } else {
+        config.acceleration * config.air_control
+    };
+    let current = character.velocity.xy();
+    let horizontal = accelerate_towards(current, target, acceleration * dt);
+    let dash_direction = if is_moving { direction } else { facing };
+    let horizontal = try_dash(config, character, dash_direction, horizontal, dt);
+    try_jump(config, character, dt);
+
+    character.velocity = Vector3::new(
+        horizontal.x,
+        horizontal.y,
+        character.velocity.z + gravity * dt,
+    );
+    character.velocity
+}
+
+/// Updates the character after the controller resolved its movement against the world, so that
+/// running into a wall or landing on the ground cancels the blocked part of the velocity.
+pub fn apply_movement(
+    character: &mut CharacterState,
+    desired_translation: Vector3<f32>,
+    movement: &EffectiveCharacterMovement,
+    dt: f32,
+) {
+    character.grounded = movement.grounded;
+    if character.grounded {
+        character.airborne_ticks = 0;
+    } else {
+        character.airborne_ticks = character.airborne_ticks.saturating_add(1);
+    }
+    character.velocity.x = movement.translation.x / dt;
+    character.velocity.y = movement.translation.y / dt;
+
+    let is_falling = character.velocity.z <= 0.0;
+    let hit_ceiling = !is_falling && movement.translation.z < desired_translation.z * 0.5;
+    if (character.grounded && is_falling) || hit_ceiling {
+        character.velocity.z = 0.0;
+    }
+    if character.grounded && is_falling {
+        character.jumped = false;
+    }
+}
+
+fn accelerate_towards(current: Vector2<f32>, target: Vector2<f32>, max_delta: f32) -> Vector2<f32> {
+    let delta = target - current;
+    if delta.norm() <= max_delta {
+        target
+    } else {
+        current + delta.normalize() * max_delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+    const GRAVITY: f32 = -9.81;
+
+    // steps the character as if the floor stopped it whenever `grounded`, and nothing else did
+    fn tick(config: &MovementConfig, character: &mut CharacterState, grounded: bool) {
+        let velocity = step_velocity(config, character, Vector2::x(), 1.0, GRAVITY, DT);
+        let desired_translation = velocity * DT;
+        let mut translation = desired_translation;
+        if grounded && translation.z < 0.0 {
+            translation.z = 0.0;
+        }
+        let movement = EffectiveCharacterMovement {
+            translation,
+            grounded,
+            is_sliding_down_slope: false,
+        };
+        apply_movement(character, desired_translation, &movement, DT);
+    }
+
+    // a character that has been falling for `airborne_ticks` after walking off a ledge
+    fn falling_for(config: &MovementConfig, airborne_ticks: usize) -> CharacterState {
+        let mut character = init_character(config, (0.0, 0.0, 0.0), 2.0);
+        tick(config, &mut character, true);
+        for _ in 0..airborne_ticks {
+            tick(config, &mut character, false);
+        }
+        character
+    }
+
+    #[test]
+    fn a_jump_just_after_walking_off_a_ledge_is_allowed() {
+        let config = MovementConfig::default();
+        let mut character = falling_for(&config, 3);
+
+        request_jump(&mut character);
+        tick(&config, &mut character, false);
+
+        assert!(character.jumped);
+        assert!(character.velocity.z > 0.0);
+    }
+
+    #[test]
+    fn a_jump_after_the_coyote_time_is_not_performed_in_mid_air() {
+        let config = MovementConfig::default();
+        let mut character = falling_for(&config, 10);
+
+        request_jump(&mut character);
+        tick(&config, &mut character, false);
+
+        assert!(!character.jumped);
+        assert!(character.velocity.z < 0.0);
+    }
+
+    #[test]
+    fn a_jump_pressed_just_before_landing_is_performed_on_landing() {
+        let config = MovementConfig::default();
+        let mut character = falling_for(&config, 10);
+
+        request_jump(&mut character);
+        tick(&config, &mut character, false);
+        tick(&config, &mut character, true);
+        assert!(!character.jumped);
+
+        tick(&config, &mut character, false);
+        assert!(character.jumped);
+        assert!(character.velocity.z > 0.0);
+    }
+
+    #[test]
+    fn a_jump_pressed_long_before_landing_is_forgotten() {
+        let config = MovementConfig::default();
+        let mut character = falling_for(&config, 10);
+
+        request_jump(&mut character);
+        for _ in 0..10 {
+            tick(&config, &mut character, false);
+        }
+        tick(&config, &mut character, true);
+        tick(&config, &mut character, true);
+
+        assert!(!character.jumped);
+        assert_eq!(character.buffered_jump_ticks, None);
+    }
+
+    #[test]
+    fn aim_pitch_is_clamped_to_the_configured_limit() {
+        let config = MovementConfig::default();
+
+        assert_eq!(get_aim(&config, 0.0, 30.0).pitch, 30.0);
+        assert_eq!(get_aim(&config, 0.0, 120.0).pitch, 85.0);
+        assert_eq!(get_aim(&config, 0.0, -120.0).pitch, -85.0);
+    }
+
+    #[test]
+    fn aim_pitch_never_goes_past_straight_up_or_down() {
+        let config = MovementConfig {
+            max_aim_pitch_degrees: 120.0,
+            ..MovementConfig::default()
+        };
+
+        assert_eq!(get_aim(&config, 0.0, 100.0).pitch, 90.0);
+        assert_eq!(get_aim(&config, 0.0, -100.0).pitch, -90.0);
+    }
+
+    #[test]
+    fn aim_yaw_is_wrapped_into_a_single_turn() {
+        let config = MovementConfig::default();
+
+        assert_eq!(get_aim(&config, -90.0, 0.0).yaw, 270.0);
+        assert_eq!(get_aim(&config, 450.0, 0.0).yaw, 90.0);
+    }
+}
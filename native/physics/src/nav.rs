@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+// path costs are kept in whole units so that ties break the same way on every platform
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+// a path may start or end this many cells away from a point that is itself blocked, such as a
+// player standing right against a wall
+const MAX_SNAP_CELLS: i64 = 3;
+
+/// A grid over the arena marking which cells a player can stand in.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct NavGrid {
+    cell_size: f32,
+    // world position of the corner of cell (0, 0)
+    origin: (f32, f32),
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+}
+
+type Cell = (usize, usize);
+
+/// Builds a square grid of the given width centred on the origin. An obstacle can only block the
+/// cells whose centres lie within its bounds, given as its lowest and highest corner, and `blocks`
+/// is asked about the centre of each of those.
+pub fn build<T>(
+    width: f32,
+    cell_size: f32,
+    obstacles: &[T],
+    get_bounds: impl Fn(&T) -> ((f32, f32), (f32, f32)),
+    blocks: impl Fn(&T, (f32, f32)) -> bool,
+) -> NavGrid {
+    let columns = (width / cell_size).ceil().max(1.0) as usize;
+    let rows = columns;
+    let mut grid = NavGrid {
+        cell_size,
+        origin: (-width / 2.0, -width / 2.0),
+        columns,
+        rows,
+        blocked: vec![false; columns * rows],
+    };
+
+    for obstacle in obstacles {
+        let (lowest, highest) = get_bounds(obstacle);
+        let columns_covered = get_covered_range(
+            lowest.0 - grid.origin.0,
+            highest.0 - grid.origin.0,
+            cell_size,
+            columns,
+        );
+        let rows_covered = get_covered_range(
+            lowest.1 - grid.origin.1,
+            highest.1 - grid.origin.1,
+            cell_size,
+            rows,
+        );
+        for row in rows_covered {
+            for column in columns_covered.clone() {
+                let index = get_index(&grid, (column, row));
+                if !grid.blocked[index] && blocks(obstacle, get_cell_center(&grid, (column, row))) {
+                    grid.blocked[index] = true;
+                }
+            }
+        }
+    }
+    grid
+}
+
+// the cells along one axis whose centres lie between `low` and `high`, both measured from the
+// grid's edge
+fn get_covered_range(low: f32, high: f32, cell_size: f32, count: usize) -> Range<usize> {
+    let first = (low / cell_size - 0.5).ceil().max(0.0);
+    let end = ((high / cell_size - 0.5).floor() + 1.0).clamp(0.0, count as f32);
+    first as usize..end as usize
+}
+
+/// Finds the shortest route between two points on the grid, moving between neighbouring cells
+/// without cutting blocked corners. Returns the waypoints to walk through after `from`, ending at
+/// `to` or the nearest free spot to it, or `None` if there is no way there.
+pub fn find_path(grid: &NavGrid, from: (f32, f32), to: (f32, f32)) -> Option<Vec<(f32, f32)>> {
+    let start = get_nearest_free_cell(grid, from)?;
+    let goal = get_nearest_free_cell(grid, to)?;
+
+    let mut cost_to = vec![u32::MAX; grid.blocked.len()];
+    let mut came_from: Vec<Option<usize>> = vec![None; grid.blocked.len()];
+    let mut open = BinaryHeap::new();
+    cost_to[get_index(grid, start)] = 0;
+    open.push(Reverse((
+        estimate_cost(start, goal),
+        get_index(grid, start),
+    )));
+
+    while let Some(Reverse((_estimate, index))) = open.pop() {
+        let cell = (index % grid.columns, index / grid.columns);
+        if cell == goal {
+            return Some(get_waypoints(grid, &came_from, index, to));
+        }
+        for (neighbour, step_cost) in get_neighbours(grid, cell) {
+            let neighbour_index = get_index(grid, neighbour);
+            let cost = cost_to[index] + step_cost;
+            if cost < cost_to[neighbour_index] {
+                cost_to[neighbour_index] = cost;
+                came_from[neighbour_index] = Some(index);
+                open.push(Reverse((
+                    cost + estimate_cost(neighbour, goal),
+                    neighbour_index,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+fn get_waypoints(
+    grid: &NavGrid,
+    came_from: &[Option<usize>],
+    goal_index: usize,
+    to: (f32, f32),
+) -> Vec<(f32, f32)> {
+    let mut cells = vec![goal_index];
+    while let Some(previous) = came_from[*cells.last().unwrap()] {
+        cells.push(previous);
+    }
+    cells.reverse();
+
+    // only the cells where the route turns are kept, and the start cell is where `from` already is
+    let cells: Vec<Cell> = cells
+        .iter()
+        .map(|index| (index % grid.columns, index / grid.columns))
+        .collect();
+    let mut waypoints: Vec<(f32, f32)> = cells
+        .windows(3)
+        .filter(|window| get_step(window[0], window[1]) != get_step(window[1], window[2]))
+        .map(|window| get_cell_center(grid, window[1]))
+        .collect();
+    // a goal inside an obstacle is swapped for the free cell next to it
+    let goal = cells[cells.len() - 1];
+    if get_cell(grid, to) == Some(goal) {
+        waypoints.push(to);
+    } else {
+        waypoints.push(get_cell_center(grid, goal));
+    }
+    waypoints
+}
+
+fn get_step(from: Cell, to: Cell) -> (i64, i64) {
+    (to.0 as i64 - from.0 as i64, to.1 as i64 - from.1 as i64)
+}
+
+fn get_neighbours(grid: &NavGrid, (column, row): Cell) -> Vec<(Cell, u32)> {
+    let mut neighbours = Vec::with_capacity(8);
+    for (dx, dy) in [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ] {
+        let neighbour = match offset_cell(grid, (column, row), dx, dy) {
+            Some(neighbour) if !is_blocked(grid, neighbour) => neighbour,
+            _ => continue,
+        };
+        if dx != 0 && dy != 0 {
+            // squeezing diagonally between two blocked cells would clip both corners
+            let is_side_free = |dx, dy| {
+                offset_cell(grid, (column, row), dx, dy).is_some_and(|side| !is_blocked(grid, side))
+            };
+            if !is_side_free(dx, 0) || !is_side_free(0, dy) {
+                continue;
+            }
+            neighbours.push((neighbour, DIAGONAL_COST));
+        } else {
+            neighbours.push((neighbour, STRAIGHT_COST));
+        }
+    }
+    neighbours
+}
+
+// the octile distance, which never overestimates on an eight-way grid
+fn estimate_cost(from: Cell, to: Cell) -> u32 {
+    let dx = (from.0 as i64 - to.0 as i64).unsigned_abs() as u32;
+    let dy = (from.1 as i64 - to.1 as i64).unsigned_abs() as u32;
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+fn get_nearest_free_cell(grid: &NavGrid, point: (f32, f32)) -> Option<Cell> {
+    let cell = get_cell(grid, point)?;
+    (0..=MAX_SNAP_CELLS).find_map(|distance| {
+        // the ring of cells `distance` away, nearest first
+        let mut ring: Vec<(i64, Cell)> = (-distance..=distance)
+            .flat_map(|dx| (-distance..=distance).map(move |dy| (dx, dy)))
+            .filter(|(dx, dy)| dx.abs().max(dy.abs()) == distance)
+            .filter_map(|(dx, dy)| Some((dx * dx + dy * dy, offset_cell(grid, cell, dx, dy)?)))
+            .filter(|(_distance, ring_cell)| !is_blocked(grid, *ring_cell))
+            .collect();
+        ring.sort();
+        ring.first().map(|(_distance, ring_cell)| *ring_cell)
+    })
+}
+
+fn offset_cell(grid: &NavGrid, (column, row): Cell, dx: i64, dy: i64) -> Option<Cell> {
+    let column = usize::try_from(column as i64 + dx).ok()?;
+    let row = usize::try_from(row as i64 + dy).ok()?;
+    (column < grid.columns && row < grid.rows).then_some((column, row))
+}
+
+fn get_cell(grid: &NavGrid, (x, y): (f32, f32)) -> Option<Cell> {
+    let column = ((x - grid.origin.0) / grid.cell_size).floor();
+    let row = ((y - grid.origin.1) / grid.cell_size).floor();
+    if column < 0.0 || row < 0.0 {
+        return None;
+    }
+    offset_cell(grid, (0, 0), column as i64, row as i64)
+}
+
+fn get_cell_center(grid: &NavGrid, (column, row): Cell) -> (f32, f32) {
+    (
+        grid.origin.0 + (column as f32 + 0.5) * grid.cell_size,
+        grid.origin.1 + (row as f32 + 0.5) * grid.cell_size,
+    )
+}
+
+fn get_index(grid: &NavGrid, (column, row): Cell) -> usize {
+    row * grid.columns + column
+}
+
+fn is_blocked(grid: &NavGrid, cell: Cell) -> bool {
+    grid.blocked[get_index(grid, cell)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_walkable(grid: &NavGrid, point: (f32, f32)) -> bool {
+        get_cell(grid, point).is_some_and(|cell| !is_blocked(grid, cell))
+    }
+
+    type Rectangle = ((f32, f32), (f32, f32));
+
+    fn build_with_walls(walls: &[Rectangle]) -> NavGrid {
+        build(20.0, 1.0, walls, |wall| *wall, |_wall, _center| true)
+    }
+
+    // a 20m arena with a wall along x = 0 from y = -10 up to y = 6
+    fn walled_grid() -> NavGrid {
+        build_with_walls(&[((-1.0, -10.0), (1.0, 6.0))])
+    }
+
+    #[test]
+    fn an_open_path_goes_straight_to_the_goal() {
+        let grid = build_with_walls(&[]);
+
+        assert_eq!(
+            find_path(&grid, (-5.5, -5.5), (4.5, -5.5)),
+            Some(vec![(4.5, -5.5)])
+        );
+    }
+
+    #[test]
+    fn a_path_goes_around_a_wall() {
+        let grid = walled_grid();
+        let path = find_path(&grid, (-5.5, -5.5), (5.5, -5.5)).unwrap();
+
+        assert_eq!(path.last(), Some(&(5.5, -5.5)));
+        assert!(path.iter().any(|(_x, y)| *y >= 6.0));
+        // every leg between waypoints stays clear of the wall
+        let mut previous = (-5.5, -5.5);
+        for waypoint in path {
+            for sample in 0..=20 {
+                let t = sample as f32 / 20.0;
+                let point = (
+                    previous.0 + (waypoint.0 - previous.0) * t,
+                    previous.1 + (waypoint.1 - previous.1) * t,
+                );
+                assert!(is_walkable(&grid, point), "{:?} is blocked", point);
+            }
+            previous = waypoint;
+        }
+    }
+
+    #[test]
+    fn there_is_no_path_into_an_enclosed_area() {
+        // a square room from 3m to 5m out, with no way in
+        let grid = build_with_walls(&[
+            ((-5.0, -5.0), (5.0, -3.0)),
+            ((-5.0, 3.0), (5.0, 5.0)),
+            ((-5.0, -5.0), (-3.0, 5.0)),
+            ((3.0, -5.0), (5.0, 5.0)),
+        ]);
+
+        assert_eq!(find_path(&grid, (0.5, 0.5), (8.5, 8.5)), None);
+        assert!(find_path(&grid, (0.5, 0.5), (-2.5, 2.5)).is_some());
+    }
+
+    #[test]
+    fn a_path_can_start_just_inside_an_obstacle() {
+        let grid = walled_grid();
+
+        assert!(find_path(&grid, (0.5, -5.5), (-5.5, -5.5)).is_some());
+        assert_eq!(find_path(&grid, (50.0, 0.0), (-5.5, -5.5)), None);
+    }
+}
@@ -1,20 +1,29 @@
 use crate::body;
+use crate::rotation::EulerDegrees;
 use crossbeam::channel::Receiver;
+use rapier3d::control::{EffectiveCharacterMovement, KinematicCharacterController};
 use rapier3d::dynamics::{ImpulseJointSet, IntegrationParameters, RigidBodySet};
+use rapier3d::parry::query;
 use rapier3d::prelude::{
-    CollisionEvent, MultibodyJointSet, QueryPipeline, RigidBody, RigidBodyHandle,
+    ActiveCollisionTypes, ColliderBuilder, ColliderHandle, CollisionEvent, InteractionGroups,
+    Isometry, MultibodyJointSet, Point, QueryFilter, QueryPipeline, Ray, RigidBody,
+    RigidBodyHandle, SharedShape,
 };
 use rapier3d::{
-    na::{UnitQuaternion, Vector3},
+    na::{Vector2, Vector3},
     prelude::{
         CCDSolver, ChannelEventCollector, ColliderSet, DefaultBroadPhase, IslandManager,
         NarrowPhase, PhysicsPipeline,
     },
 };
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::f32::consts::PI;
 
+/// Everything needed to carry on simulating. The pipeline only holds scratch buffers and the
+/// collision channel is process-local, so both are rebuilt rather than serialised.
+#[derive(Serialize, Deserialize)]
 pub struct PhysicsWorld {
+    #[serde(skip)]
     pipeline: PhysicsPipeline,
     island_manager: IslandManager,
     gravity: Vector3<f32>,
@@ -27,9 +36,26 @@ pub struct PhysicsWorld {
     multibody_joint_set: MultibodyJointSet,
     ccd_solver: CCDSolver,
     query_pipeline: QueryPipeline,
+    #[serde(skip)]
     physics_hooks: (),
+    #[serde(skip)]
+    collision_channel: CollisionChannel,
+}
+
+struct CollisionChannel {
     event_handler: ChannelEventCollector,
-    collision_receiver: Receiver<CollisionEvent>,
+    receiver: Receiver<CollisionEvent>,
+}
+
+impl Default for CollisionChannel {
+    fn default() -> Self {
+        let (contact_send, _contact_receiver) = crossbeam::channel::unbounded();
+        let (collision_send, receiver) = crossbeam::channel::unbounded();
+        CollisionChannel {
+            event_handler: ChannelEventCollector::new(collision_send, contact_send),
+            receiver,
+        }
+    }
 }
 
 const GRAVITY: f32 = -9.80665;
@@ -46,9 +72,6 @@ pub fn init() -> PhysicsWorld {
     let ccd_solver = CCDSolver::new();
     let query_pipeline = QueryPipeline::new();
     let physics_hooks = ();
-    let (contact_send, _contact_receiver) = crossbeam::channel::unbounded();
-    let (collision_send, collision_receiver) = crossbeam::channel::unbounded();
-    let event_handler = ChannelEventCollector::new(collision_send, contact_send);
 
     PhysicsWorld {
         pipeline,
@@ -64,8 +87,7 @@ pub fn init() -> PhysicsWorld {
         ccd_solver,
         query_pipeline,
         physics_hooks,
-        event_handler,
-        collision_receiver,
+        collision_channel: CollisionChannel::default(),
     }
 }
 
@@ -83,11 +105,11 @@ pub fn step(physics_world: &mut PhysicsWorld) -> Vec<CollisionEvent> {
         &mut physics_world.ccd_solver,
         Some(&mut physics_world.query_pipeline),
         &physics_world.physics_hooks,
-        &physics_world.event_handler,
+        &physics_world.collision_channel.event_handler,
     );
 
     let mut collisions = Vec::new();
-    while let Ok(collision_event) = physics_world.collision_receiver.try_recv() {
+    while let Ok(collision_event) = physics_world.collision_channel.receiver.try_recv() {
         collisions.push(collision_event);
     }
     collisions
@@ -97,6 +119,10 @@ pub fn get_dt(physics_world: &PhysicsWorld) -> f32 {
     physics_world.integration_parameters.dt
 }
 
+pub fn get_gravity(physics_world: &PhysicsWorld) -> f32 {
+    physics_world.gravity.z
+}
+
 pub fn remove_body(physics_world: &mut PhysicsWorld, rigid_body_handle: RigidBodyHandle) {
     physics_world.bodies.remove(
         rigid_body_handle,
@@ -117,11 +143,12 @@ pub fn get_bodies(physics_world: &PhysicsWorld) -> &RigidBodySet {
 }
 
 pub fn get_active_handles(physics_world: &PhysicsWorld) -> HashSet<RigidBodyHandle> {
-    physics_world
-        .island_manager
+    let island_manager = &physics_world.island_manager;
+    island_manager
         .active_dynamic_bodies()
-        .into_iter()
-        .map(|handle| handle.clone())
+        .iter()
+        .chain(island_manager.active_kinematic_bodies())
+        .copied()
         .collect()
 }
 
@@ -142,6 +169,274 @@ pub fn add_body(physics_world: &mut PhysicsWorld, body: &body::Body) -> RigidBod
     body_handle
 }
 
+/// Adds a free-standing sensor box, detecting any body that enters it without colliding.
+pub fn add_sensor(
+    physics_world: &mut PhysicsWorld,
+    center: Vector3<f32>,
+    half_extents: Vector3<f32>,
+) -> ColliderHandle {
+    let sensor = ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z)
+        .translation(center)
+        .sensor(true)
+        .active_collision_types(ActiveCollisionTypes::all())
+        .build();
+    physics_world.colliders.insert(sensor)
+}
+
+pub fn get_intersecting_bodies(
+    physics_world: &PhysicsWorld,
+    sensor_handle: ColliderHandle,
+) -> Vec<RigidBodyHandle> {
+    physics_world
+        .narrow_phase
+        .intersection_pairs_with(sensor_handle)
+        .filter(|(_a, _b, intersecting)| *intersecting)
+        .map(|(a, b, _intersecting)| if a == sensor_handle { b } else { a })
+        .filter_map(|collider_handle| physics_world.colliders.get(collider_handle))
+        .filter_map(|collider| collider.parent())
+        .collect()
+}
+
+/// Pushes every dynamic body within `radius` of `center` directly away from it, with the impulse
+/// falling off linearly to nothing at the edge. Returns the bodies that were pushed.
+pub fn apply_explosion(
+    world: &mut PhysicsWorld,
+    center: Vector3<f32>,
+    radius: f32,
+    impulse: f32,
+) -> Vec<RigidBodyHandle> {
+    let blast = SharedShape::ball(radius);
+    let mut handles = Vec::new();
+    world.query_pipeline.intersections_with_shape(
+        &world.bodies,
+        &world.colliders,
+        &Isometry::translation(center.x, center.y, center.z),
+        &*blast,
+        QueryFilter::only_dynamic().exclude_sensors(),
+        |collider_handle| {
+            if let Some(parent) = world
+                .colliders
+                .get(collider_handle)
+                .and_then(|collider| collider.parent())
+            {
+                handles.push(parent);
+            }
+            true
+        },
+    );
+    handles.sort_by_key(|handle| handle.into_raw_parts());
+    handles.dedup();
+
+    for handle in &handles {
+        if let Some(body) = world.bodies.get_mut(*handle) {
+            let offset = body.center_of_mass() - Point::from(center);
+            let distance = offset.norm();
+            let direction = offset
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::z);
+            let strength = impulse * (1.0 - distance / radius).max(0.0);
+            body.apply_impulse(direction * strength, true);
+        }
+    }
+
+    handles
+}
+
+/// Checks whether the body's collider would fit at `translation` without touching anything else.
+pub fn is_space_free(
+    physics_world: &PhysicsWorld,
+    handle: &RigidBodyHandle,
+    translation: Vector3<f32>,
+) -> bool {
+    let collider = match get_body(physics_world, handle)
+        .and_then(|body| body.colliders().first())
+        .and_then(|collider_handle| physics_world.colliders.get(*collider_handle))
+    {
+        Some(collider) => collider,
+        None => return false,
+    };
+
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(*handle)
+        .exclude_sensors();
+    physics_world
+        .query_pipeline
+        .intersection_with_shape(
+            &physics_world.bodies,
+            &physics_world.colliders,
+            &Isometry::translation(translation.x, translation.y, translation.z),
+            collider.shape(),
+            filter,
+        )
+        .is_none()
+}
+
+/// Returns where two colliders touched during the last step, at the deepest point of their
+/// contact, or `None` if they were not in contact.
+pub fn get_contact_point(
+    world: &PhysicsWorld,
+    collider_a: ColliderHandle,
+    collider_b: ColliderHandle,
+) -> Option<Vector3<f32>> {
+    let pair = world.narrow_phase.contact_pair(collider_a, collider_b)?;
+    let (manifold, contact) = pair.find_deepest_contact()?;
+    let collider = world.colliders.get(pair.collider1)?;
+    let shape_position = collider.position() * manifold.subshape_pos1.unwrap_or_default();
+    Some((shape_position * contact.local_p1).coords)
+}
+
+/// Casts a ray from `origin` along the unit vector `direction` and returns the first body in the
+/// way within `max_distance`, along with how far away it is. Only what blocks a line of sight is
+/// considered, and the body looking is ignored.
+pub fn cast_sight_ray(
+    world: &PhysicsWorld,
+    looker: &RigidBodyHandle,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+) -> Option<(RigidBodyHandle, f32)> {
+    let ray = Ray::new(Point::from(origin), direction);
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(*looker)
+        .exclude_sensors()
+        .groups(body::get_sight_groups());
+    let (collider_handle, distance) = world.query_pipeline.cast_ray(
+        &world.bodies,
+        &world.colliders,
+        &ray,
+        max_distance,
+        true,
+        filter,
+    )?;
+    let parent = world.colliders.get(collider_handle)?.parent()?;
+    Some((parent, distance))
+}
+
+/// Returns the solid colliders of every fixed body but `ignored`, in the order they were added.
+pub fn get_fixed_colliders(
+    world: &PhysicsWorld,
+    ignored: Option<&RigidBodyHandle>,
+) -> Vec<ColliderHandle> {
+    world
+        .colliders
+        .iter()
+        .filter(|(_handle, collider)| !collider.is_sensor())
+        .filter(|(_handle, collider)| {
+            collider.parent().is_some_and(|parent| {
+                Some(&parent) != ignored
+                    && world.bodies.get(parent).is_some_and(RigidBody::is_fixed)
+            })
+        })
+        .map(|(handle, _collider)| handle)
+        .collect()
+}
+
+/// Returns the lowest and highest corner of the collider's bounding box seen from above, grown by
+/// `margin` on every side.
+pub fn get_footprint(
+    world: &PhysicsWorld,
+    collider_handle: &ColliderHandle,
+    margin: f32,
+) -> ((f32, f32), (f32, f32)) {
+    match world.colliders.get(*collider_handle) {
+        Some(collider) => {
+            let aabb = collider.compute_aabb();
+            (
+                (aabb.mins.x - margin, aabb.mins.y - margin),
+                (aabb.maxs.x + margin, aabb.maxs.y + margin),
+            )
+        }
+        None => ((0.0, 0.0), (0.0, 0.0)),
+    }
+}
+
+/// Checks whether the collider reaches into the upright box with its base centred on `base` and
+/// the given half width and height.
+pub fn is_column_blocked(
+    world: &PhysicsWorld,
+    collider_handle: &ColliderHandle,
+    base: Vector3<f32>,
+    half_width: f32,
+    height: f32,
+) -> bool {
+    let collider = match world.colliders.get(*collider_handle) {
+        Some(collider) => collider,
+        None => return false,
+    };
+    let column = SharedShape::cuboid(half_width, half_width, height / 2.0);
+    query::intersection_test(
+        &Isometry::translation(base.x, base.y, base.z + height / 2.0),
+        &*column,
+        collider.position(),
+        collider.shape(),
+    )
+    .unwrap_or(false)
+}
+
+const MAX_SURFACE_HEIGHT: f32 = 1000.0;
+
+/// Casts a ray straight down onto the given body's colliders and returns the height of the highest
+/// surface below (x, y), if any.
+pub fn get_surface_height(
+    world: &PhysicsWorld,
+    handle: &RigidBodyHandle,
+    x: f32,
+    y: f32,
+) -> Option<f32> {
+    let ray = Ray::new(Point::new(x, y, MAX_SURFACE_HEIGHT), -Vector3::z());
+    get_body(world, handle)?
+        .colliders()
+        .iter()
+        .filter_map(|collider_handle| world.colliders.get(*collider_handle))
+        .filter_map(|collider| {
+            collider
+                .shape()
+                .cast_ray(collider.position(), &ray, f32::MAX, true)
+        })
+        .map(|time_of_impact| MAX_SURFACE_HEIGHT - time_of_impact)
+        .reduce(f32::max)
+}
+
+pub fn teleport_body(
+    physics_world: &mut PhysicsWorld,
+    handle: &RigidBodyHandle,
+    translation: Vector3<f32>,
+) {
+    if let Some(existing_body) = get_body_mut(physics_world, handle) {
+        existing_body.set_translation(translation, true);
+        existing_body.set_linvel(Vector3::zeros(), true);
+        existing_body.set_angvel(Vector3::zeros(), true);
+    }
+}
+
+pub fn set_collision_groups(
+    physics_world: &mut PhysicsWorld,
+    handle: &RigidBodyHandle,
+    groups: InteractionGroups,
+) {
+    if let Some(body) = physics_world.bodies.get(*handle) {
+        for collider_handle in body.colliders() {
+            if let Some(collider) = physics_world.colliders.get_mut(*collider_handle) {
+                collider.set_collision_groups(groups);
+            }
+        }
+    }
+}
+
+pub fn set_colliders_enabled(
+    physics_world: &mut PhysicsWorld,
+    handle: &RigidBodyHandle,
+    enabled: bool,
+) {
+    if let Some(body) = physics_world.bodies.get(*handle) {
+        for collider_handle in body.colliders() {
+            if let Some(collider) = physics_world.colliders.get_mut(*collider_handle) {
+                collider.set_enabled(enabled);
+            }
+        }
+    }
+}
+
 pub fn get_body_mut<'a>(
     physics_world: &'a mut PhysicsWorld,
     handle: &RigidBodyHandle,
@@ -149,24 +444,127 @@ pub fn get_body_mut<'a>(
     physics_world.bodies.get_mut(*handle)
 }
 
-pub fn move_body(world: &mut PhysicsWorld, handle: &RigidBodyHandle, x: f32, y: f32) {
+pub fn rotate_body(world: &mut PhysicsWorld, handle: &RigidBodyHandle, rotation_angle: f32) {
     if let Some(existing_body) = get_body_mut(world, handle) {
-        existing_body.set_translation(Vector3::new(x, y, existing_body.translation().z), true);
+        existing_body.set_rotation(EulerDegrees::from_yaw(rotation_angle).to_quaternion(), true);
     }
 }
 
-pub fn rotate_body(world: &mut PhysicsWorld, handle: &RigidBodyHandle, rotation_angle: f32) {
-    if let Some(existing_body) = get_body_mut(world, handle) {
-        let angle_radians = rotation_angle * (PI / 180.0);
-        let rotation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle_radians);
+/// Returns the horizontal direction the body is facing. Bodies face along the y axis when unrotated.
+pub fn get_facing(world: &PhysicsWorld, handle: &RigidBodyHandle) -> Vector2<f32> {
+    match get_body(world, handle) {
+        Some(body) => (body.rotation() * Vector3::y()).xy(),
+        None => Vector2::zeros(),
+    }
+}
 
-        existing_body.set_rotation(rotation, true);
+pub fn set_collider_shape(world: &mut PhysicsWorld, handle: &RigidBodyHandle, shape: SharedShape) {
+    let collider_handle = match get_body(world, handle).and_then(|body| body.colliders().first()) {
+        Some(collider_handle) => *collider_handle,
+        None => return,
+    };
+    if let Some(collider) = world.colliders.get_mut(collider_handle) {
+        collider.set_shape(shape);
     }
 }
 
-pub fn jump_body(world: &mut PhysicsWorld, handle: &RigidBodyHandle, linvelz: f32) {
-    if let Some(existing_body) = get_body_mut(world, handle) {
-        let linvel = existing_body.linvel();
-        existing_body.set_linvel(Vector3::new(linvel.x, linvel.y, linvelz), true);
+/// Swaps a player's capsule for one of a different height while keeping its feet in place. Growing
+/// the capsule fails if the taller shape would overlap the world.
+pub fn resize_player(
+    world: &mut PhysicsWorld,
+    handle: &RigidBodyHandle,
+    current_height: f32,
+    height: f32,
+) -> bool {
+    let (position, collider_handle) = match get_body(world, handle)
+        .and_then(|body| Some((*body.position(), *body.colliders().first()?)))
+    {
+        Some(found) => found,
+        None => return false,
+    };
+    let collider = match world.colliders.get(collider_handle) {
+        Some(collider) => collider,
+        None => return false,
+    };
+
+    let shape = body::get_player_shape(height);
+    let mut resized_position = position;
+    resized_position.translation.vector.z += (height - current_height) / 2.0;
+
+    if height > current_height {
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(*handle)
+            .exclude_sensors()
+            .groups(collider.collision_groups());
+        let is_blocked = world
+            .query_pipeline
+            .intersection_with_shape(
+                &world.bodies,
+                &world.colliders,
+                &resized_position,
+                &*shape,
+                filter,
+            )
+            .is_some();
+        if is_blocked {
+            return false;
+        }
     }
+
+    set_collider_shape(world, handle, shape);
+    if let Some(body) = world.bodies.get_mut(*handle) {
+        body.set_position(resized_position, true);
+        body.set_next_kinematic_position(resized_position);
+    }
+
+    true
+}
+
+/// Resolves a kinematic character's desired translation against the world and schedules the
+/// resulting position for the next step.
+pub fn move_character(
+    world: &mut PhysicsWorld,
+    controller: &KinematicCharacterController,
+    handle: &RigidBodyHandle,
+    desired_translation: Vector3<f32>,
+) -> Option<EffectiveCharacterMovement> {
+    let body = world.bodies.get(*handle)?;
+    let collider = world.colliders.get(*body.colliders().first()?)?;
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(*handle)
+        .exclude_sensors()
+        .groups(collider.collision_groups());
+
+    let position = *body.position();
+    let mass = body.mass();
+    let mut collisions = Vec::new();
+    let movement = controller.move_shape(
+        world.integration_parameters.dt,
+        &world.bodies,
+        &world.colliders,
+        &world.query_pipeline,
+        collider.shape(),
+        &position,
+        desired_translation,
+        filter,
+        |collision| collisions.push(collision),
+    );
+
+    // the controller treats dynamic bodies as walls, so push back on whatever it ran into
+    controller.solve_character_collision_impulses(
+        world.integration_parameters.dt,
+        &mut world.bodies,
+        &world.colliders,
+        &world.query_pipeline,
+        collider.shape(),
+        mass,
+        &collisions,
+        filter,
+    );
+
+    if let Some(body) = world.bodies.get_mut(*handle) {
+        body.set_next_kinematic_translation(position.translation.vector + movement.translation);
+    }
+
+    Some(movement)
 }
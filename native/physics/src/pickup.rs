@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum PickupEffect {
+    Health {
+        amount: i32,
+    },
+    Ammo {
+        amount: i32,
+    },
+    Speed {
+        multiplier: f32,
+        duration_seconds: f32,
+    },
+    Damage {
+        multiplier: f32,
+        duration_seconds: f32,
+    },
+    Shield {
+        amount: f32,
+        duration_seconds: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Pickup {
+    #[serde(flatten)]
+    pub effect: PickupEffect,
+    pub respawn_seconds: f32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "buff", rename_all = "snake_case")]
+pub enum BuffKind {
+    Speed { multiplier: f32 },
+    Damage { multiplier: f32 },
+    // absorbs this much damage before hp is touched
+    Shield { remaining: f32 },
+}
+
+/// A buff as reported in body updates.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Buff {
+    #[serde(flatten)]
+    pub kind: BuffKind,
+    pub seconds_remaining: f32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ActiveBuff {
+    pub kind: BuffKind,
+    pub expires_at_tick: u64,
+}
+
+/// Converts a timed pickup effect into the buff it grants, if any.
+pub fn get_buff(effect: PickupEffect) -> Option<(BuffKind, f32)> {
+    match effect {
+        PickupEffect::Speed {
+            multiplier,
+            duration_seconds,
+        } => Some((BuffKind::Speed { multiplier }, duration_seconds)),
+        PickupEffect::Damage {
+            multiplier,
+            duration_seconds,
+        } => Some((BuffKind::Damage { multiplier }, duration_seconds)),
+        PickupEffect::Shield {
+            amount,
+            duration_seconds,
+        } => Some((BuffKind::Shield { remaining: amount }, duration_seconds)),
+        PickupEffect::Health { .. } | PickupEffect::Ammo { .. } => None,
+    }
+}
+
+/// Adds a buff, replacing any active buff of the same kind so stacking pickups refreshes the timer
+/// rather than compounding the effect.
+pub fn add_buff(buffs: &mut Vec<ActiveBuff>, kind: BuffKind, expires_at_tick: u64) {
+    buffs.retain(|buff| std::mem::discriminant(&buff.kind) != std::mem::discriminant(&kind));
+    buffs.push(ActiveBuff {
+        kind,
+        expires_at_tick,
+    });
+}
+
+pub fn get_speed_multiplier(buffs: &[ActiveBuff]) -> f32 {
+    buffs
+        .iter()
+        .map(|buff| match buff.kind {
+            BuffKind::Speed { multiplier } => multiplier,
+            _ => 1.0,
+        })
+        .product()
+}
+
+pub fn get_damage_multiplier(buffs: &[ActiveBuff]) -> f32 {
+    buffs
+        .iter()
+        .map(|buff| match buff.kind {
+            BuffKind::Damage { multiplier } => multiplier,
+            _ => 1.0,
+        })
+        .product()
+}
+
+/// Soaks up as much of `damage` as the active shields allow, returning what is left over.
+pub fn absorb_damage(buffs: &mut Vec<ActiveBuff>, damage: f32) -> f32 {
+    let mut damage = damage;
+    for buff in buffs.iter_mut() {
+        if let BuffKind::Shield { remaining } = &mut buff.kind {
+            let absorbed = remaining.min(damage);
+            *remaining -= absorbed;
+            damage -= absorbed;
+        }
+    }
+    buffs.retain(|buff| !matches!(buff.kind, BuffKind::Shield { remaining } if remaining <= 0.0));
+
+    damage
+}
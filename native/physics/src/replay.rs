@@ -0,0 +1,186 @@
+use crate::checkpoint;
+use crate::config::Config;
+use crate::game;
+use crate::mode;
+use crate::user_input::{self, UserInput};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// The first line of a replay file: everything needed to rebuild the match before any input.
+#[derive(Deserialize, Serialize)]
+struct Header {
+    seed: u64,
+    config: Config,
+    // set when the recorded match was resumed from a checkpoint rather than started fresh; the
+    // file has to be kept as it was for the replay to work
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checkpoint: Option<String>,
+}
+
+/// One line per tick: the inputs applied before it and the state hash after it.
+#[derive(Deserialize, Serialize)]
+struct TickRecord {
+    tick: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inputs: Vec<UserInput>,
+    hash: u64,
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+pub fn start_recording(
+    path: &str,
+    seed: u64,
+    config: &Config,
+    checkpoint: Option<String>,
+) -> Result<Recorder, String> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut recorder = Recorder {
+        writer: BufWriter::new(file),
+    };
+    let header = Header {
+        seed,
+        config: config.clone(),
+        checkpoint,
+    };
+    write_line(&mut recorder, &header)?;
+    Ok(recorder)
+}
+
+/// Appends a tick that has just been stepped. Each line is flushed straight away so that a
+/// recording survives the process crashing, which is when it is wanted most.
+pub fn record_tick(
+    recorder: &mut Recorder,
+    game_state: &game::Game,
+    inputs: Vec<UserInput>,
+) -> Result<(), String> {
+    let record = TickRecord {
+        tick: game::get_tick(game_state),
+        inputs,
+        hash: game::state_hash(game_state),
+    };
+    write_line(recorder, &record)
+}
+
+fn write_line<T: Serialize>(recorder: &mut Recorder, line: &T) -> Result<(), String> {
+    serde_json::to_writer(&mut recorder.writer, line).map_err(|err| err.to_string())?;
+    recorder
+        .writer
+        .write_all(b"\n")
+        .and_then(|_| recorder.writer.flush())
+        .map_err(|err| err.to_string())
+}
+
+/// Re-simulates a recording tick by tick, feeding in the recorded inputs, and checks the state
+/// hash after every tick. Returns the number of ticks that matched, or where the two diverged.
+pub fn verify(path: &str) -> Result<u64, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let mut lines = BufReader::new(file).lines();
+    let header: Header = match lines.next() {
+        Some(line) => parse_line(line)?,
+        None => return Err(String::from("Replay file is empty")),
+    };
+
+    let (mut game_state, _game_mode) = match &header.checkpoint {
+        Some(checkpoint_path) => checkpoint::load(checkpoint_path, &header.config)?,
+        None => mode::start_match(&header.config, header.seed),
+    };
+
+    let mut ticks_verified = 0;
+    for line in lines {
+        let record: TickRecord = parse_line(line)?;
+        user_input::apply_user_input(&record.inputs, &mut game_state);
+        let bot_inputs = game::get_bot_inputs(&mut game_state);
+        user_input::apply_user_input(&bot_inputs, &mut game_state);
+        game::step(&mut game_state);
+        game::take_events(&mut game_state);
+        game::take_snapshot_requests(&mut game_state);
+
+        let hash = game::state_hash(&game_state);
+        if game::get_tick(&game_state) != record.tick || hash != record.hash {
+            return Err(format!(
+                "Replay diverged at tick {}: recorded hash {:016x}, replayed tick {} with hash {:016x}",
+                record.tick,
+                record.hash,
+                game::get_tick(&game_state),
+                hash
+            ));
+        }
+        ticks_verified += 1;
+    }
+
+    Ok(ticks_verified)
+}
+
+fn parse_line<T: for<'de> Deserialize<'de>>(line: std::io::Result<String>) -> Result<T, String> {
+    let line = line.map_err(|err| err.to_string())?;
+    serde_json::from_str(&line).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fixtures;
+    use std::fs;
+
+    fn parse_input(json: &str) -> UserInput {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn record_match(path: &str) {
+        let config = Config::default();
+        let (mut game_state, _game_mode) = mode::start_match(&config, 5);
+        let mut recorder = start_recording(path, 5, &config, None).unwrap();
+        let inputs_by_tick = vec![
+            vec![
+                parse_input(&fixtures::add_player("a", "red", (0.0, 0.0), 10)),
+                parse_input(r#"{"action":"add_bot","team_id":"blue"}"#),
+            ],
+            vec![parse_input(
+                r#"{"action":"move","id":"a","direction":[1.0,0.0]}"#,
+            )],
+            vec![],
+            vec![parse_input(r#"{"action":"jump","id":"a"}"#)],
+        ];
+
+        for inputs in inputs_by_tick.into_iter().chain((0..20).map(|_| vec![])) {
+            user_input::apply_user_input(&inputs, &mut game_state);
+            let bot_inputs = game::get_bot_inputs(&mut game_state);
+            user_input::apply_user_input(&bot_inputs, &mut game_state);
+            game::step(&mut game_state);
+            record_tick(&mut recorder, &game_state, inputs).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_recording_replays_to_the_same_hashes() {
+        let path = std::env::temp_dir().join("physics-replay-matches.jsonl");
+        let path = path.to_str().unwrap();
+        record_match(path);
+
+        assert_eq!(verify(path), Ok(24));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_replay_that_diverges_reports_the_tick() {
+        let path = std::env::temp_dir().join("physics-replay-diverges.jsonl");
+        let path = path.to_str().unwrap();
+        record_match(path);
+
+        // drop the jump, so everything after it plays out differently
+        let contents = fs::read_to_string(path).unwrap();
+        fs::write(
+            path,
+            contents.replace(r#","inputs":[{"action":"jump","id":"a"}]"#, ""),
+        )
+        .unwrap();
+
+        let err = verify(path).unwrap_err();
+        assert!(err.contains("diverged at tick 4"), "{}", err);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use rapier3d::na::UnitQuaternion;
+use serde::{Deserialize, Serialize};
+
+/// A rotation as (roll, pitch, yaw) in degrees about the x, y and z axes, applied in that order.
+/// This is the only form rotations take outside of the physics world: body updates report it and
+/// inputs send it, so converting through here in both directions always round-trips.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct EulerDegrees(pub f32, pub f32, pub f32);
+
+impl EulerDegrees {
+    pub fn from_yaw(yaw: f32) -> Self {
+        EulerDegrees(0.0, 0.0, yaw)
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.2
+    }
+
+    pub fn to_quaternion(self) -> UnitQuaternion<f32> {
+        let EulerDegrees(roll, pitch, yaw) = self;
+        UnitQuaternion::from_euler_angles(roll.to_radians(), pitch.to_radians(), yaw.to_radians())
+    }
+
+    /// Yaw is reported in [0, 360) so that an upright body's heading never jumps sign.
+    pub fn from_quaternion(rotation: &UnitQuaternion<f32>) -> Self {
+        let (roll, pitch, yaw) = rotation.euler_angles();
+        EulerDegrees(
+            roll.to_degrees(),
+            pitch.to_degrees(),
+            yaw.to_degrees().rem_euclid(360.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn assert_same_rotation(a: &UnitQuaternion<f32>, b: &UnitQuaternion<f32>) {
+        assert!(a.angle_to(b) < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn yaw_round_trips_through_quaternion() {
+        for yaw in [0.0, 1.0, 45.0, 90.0, 179.5, 180.0, 270.0, 359.0] {
+            let rotation = EulerDegrees::from_yaw(yaw);
+            let round_tripped = EulerDegrees::from_quaternion(&rotation.to_quaternion());
+
+            assert!(
+                (round_tripped.yaw() - yaw).abs() < EPSILON,
+                "{} != {}",
+                round_tripped.yaw(),
+                yaw
+            );
+            assert!(round_tripped.0.abs() < EPSILON);
+            assert!(round_tripped.1.abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn negative_yaw_is_reported_in_positive_range() {
+        let rotation = EulerDegrees::from_yaw(-90.0);
+        let round_tripped = EulerDegrees::from_quaternion(&rotation.to_quaternion());
+
+        assert!((round_tripped.yaw() - 270.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn full_rotation_round_trips_through_quaternion() {
+        let rotations = [
+            EulerDegrees(30.0, 0.0, 0.0),
+            EulerDegrees(0.0, -45.0, 0.0),
+            EulerDegrees(10.0, 20.0, 30.0),
+            EulerDegrees(-120.0, 60.0, 300.0),
+            EulerDegrees(170.0, -80.0, 5.0),
+        ];
+
+        for rotation in rotations {
+            let quaternion = rotation.to_quaternion();
+            let round_tripped = EulerDegrees::from_quaternion(&quaternion);
+
+            assert_same_rotation(&round_tripped.to_quaternion(), &quaternion);
+        }
+    }
+
+    #[test]
+    fn yaw_turns_about_the_up_axis() {
+        let rotation = EulerDegrees::from_yaw(90.0).to_quaternion();
+        let forward = rotation * rapier3d::na::Vector3::y();
+
+        assert!((forward.x + 1.0).abs() < EPSILON);
+        assert!(forward.y.abs() < EPSILON);
+        assert!(forward.z.abs() < EPSILON);
+    }
+
+    #[test]
+    fn serializes_as_a_three_element_array() {
+        let rotation = EulerDegrees(0.0, 0.0, 90.0);
+        let json = serde_json::to_string(&rotation).unwrap();
+
+        assert_eq!(json, "[0.0,0.0,90.0]");
+        assert_eq!(
+            serde_json::from_str::<EulerDegrees>(&json).unwrap(),
+            rotation
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FriendlyFire {
+    Off,
+    On,
+    Reduced { multiplier: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BodyRegion {
+    Head,
+    Torso,
+    Legs,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RegionMultipliers {
+    pub head: f32,
+    pub torso: f32,
+    pub legs: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RespawnRules {
+    pub enabled: bool,
+    pub delay_seconds: f32,
+    // when set, eliminated players come back together on the next wave after their delay
+    pub wave_interval_seconds: Option<f32>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GameRules {
+    pub friendly_fire: FriendlyFire,
+    pub self_damage: bool,
+    pub bullet_damage: f32,
+    pub region_multipliers: RegionMultipliers,
+    pub spawn_invulnerability_ms: f32,
+    pub respawn: RespawnRules,
+    // None means unlimited ammo
+    pub starting_ammo: Option<i32>,
+}
+
+impl Default for RegionMultipliers {
+    fn default() -> Self {
+        RegionMultipliers {
+            head: 1.0,
+            torso: 1.0,
+            legs: 1.0,
+        }
+    }
+}
+
+impl Default for RespawnRules {
+    fn default() -> Self {
+        RespawnRules {
+            enabled: false,
+            delay_seconds: 5.0,
+            wave_interval_seconds: None,
+        }
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            friendly_fire: FriendlyFire::Off,
+            self_damage: false,
+            bullet_damage: 1.0,
+            region_multipliers: RegionMultipliers::default(),
+            spawn_invulnerability_ms: 0.0,
+            respawn: RespawnRules::default(),
+            starting_ammo: None,
+        }
+    }
+}
+
+/// Returns the damage a bullet deals to a player, or `None` if the hit should be ignored
+/// entirely (the bullet passes on as if nothing happened).
+pub fn bullet_damage(
+    rules: &GameRules,
+    is_self_hit: bool,
+    is_same_team: bool,
+    region: BodyRegion,
+) -> Option<f32> {
+    let team_multiplier = match (is_self_hit, is_same_team, rules.friendly_fire) {
+        (true, _, _) if rules.self_damage => 1.0,
+        (true, _, _) => return None,
+        (false, false, _) => 1.0,
+        (false, true, FriendlyFire::On) => 1.0,
+        (false, true, FriendlyFire::Reduced { multiplier }) => multiplier,
+        (false, true, FriendlyFire::Off) => return None,
+    };
+
+    let region_multiplier = match region {
+        BodyRegion::Head => rules.region_multipliers.head,
+        BodyRegion::Torso => rules.region_multipliers.torso,
+        BodyRegion::Legs => rules.region_multipliers.legs,
+    };
+
+    Some(rules.bullet_damage * team_multiplier * region_multiplier)
+}
+
+/// Returns the tick at which a player eliminated on `tick` comes back, if respawning is enabled.
+pub fn get_respawn_tick(rules: &GameRules, tick: u64, tick_ms: f32) -> Option<u64> {
+    if !rules.respawn.enabled {
+        return None;
+    }
+
+    let to_ticks = |seconds: f32| (seconds * 1000.0 / tick_ms).ceil() as u64;
+    let earliest_tick = tick + to_ticks(rules.respawn.delay_seconds);
+
+    match rules.respawn.wave_interval_seconds.map(to_ticks) {
+        Some(wave_ticks) if wave_ticks > 0 => Some(earliest_tick.div_ceil(wave_ticks) * wave_ticks),
+        _ => Some(earliest_tick),
+    }
+}
+
+pub fn is_invulnerable(rules: &GameRules, ms_since_spawn: f32) -> bool {
+    ms_since_spawn < rules.spawn_invulnerability_ms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 50 ticks a second, so that seconds convert to whole ticks
+    const TICK_MS: f32 = 20.0;
+
+    fn respawning(wave_interval_seconds: Option<f32>) -> GameRules {
+        GameRules {
+            respawn: RespawnRules {
+                enabled: true,
+                delay_seconds: 5.0,
+                wave_interval_seconds,
+            },
+            ..GameRules::default()
+        }
+    }
+
+    #[test]
+    fn players_do_not_respawn_unless_it_is_enabled() {
+        assert_eq!(get_respawn_tick(&GameRules::default(), 100, TICK_MS), None);
+    }
+
+    #[test]
+    fn players_respawn_once_the_delay_is_over() {
+        assert_eq!(get_respawn_tick(&respawning(None), 100, TICK_MS), Some(350));
+    }
+
+    #[test]
+    fn waves_hold_players_back_until_the_next_wave_after_their_delay() {
+        let rules = respawning(Some(10.0));
+
+        assert_eq!(get_respawn_tick(&rules, 100, TICK_MS), Some(500));
+        assert_eq!(get_respawn_tick(&rules, 250, TICK_MS), Some(500));
+        assert_eq!(get_respawn_tick(&rules, 251, TICK_MS), Some(1000));
+    }
+}
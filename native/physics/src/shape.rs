@@ -0,0 +1,218 @@
+use rapier3d::na::{DMatrix, Isometry3, Point3, Vector3};
+use rapier3d::parry::transformation;
+use rapier3d::prelude::SharedShape;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// The geometry of a body, in the body's local frame with z up. Bodies without a descriptor fall
+/// back to the default shape for their class, sized from `dimensions`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeDescriptor {
+    Cuboid {
+        half_extents: (f32, f32, f32),
+    },
+    // stands along the z axis
+    Cylinder {
+        half_height: f32,
+        radius: f32,
+    },
+    // a ramp whose sloped face rises from the -x edge of its base to the top of its +x side
+    Wedge {
+        half_extents: (f32, f32, f32),
+    },
+    ConvexHull {
+        points: Vec<(f32, f32, f32)>,
+    },
+    // heights[row][column], with rows along y and columns along x, spread evenly over size
+    Heightfield {
+        heights: Vec<Vec<f32>>,
+        size: (f32, f32),
+    },
+    Trimesh {
+        vertices: Vec<(f32, f32, f32)>,
+        indices: Vec<[u32; 3]>,
+    },
+}
+
+/// Builds the rapier shape for a descriptor along with its pose relative to the body, or `None` if
+/// the descriptor does not describe a valid shape (degenerate hulls, empty meshes, out of range
+/// indices).
+pub fn to_shared_shape(descriptor: &ShapeDescriptor) -> Option<(Isometry3<f32>, SharedShape)> {
+    match descriptor {
+        ShapeDescriptor::Cuboid { half_extents } => Some((
+            Isometry3::identity(),
+            SharedShape::cuboid(half_extents.0, half_extents.1, half_extents.2),
+        )),
+        ShapeDescriptor::Cylinder {
+            half_height,
+            radius,
+        } => Some((
+            get_z_up_pose(),
+            SharedShape::cylinder(*half_height, *radius),
+        )),
+        ShapeDescriptor::Wedge { half_extents } => {
+            let (x, y, z) = *half_extents;
+            let wedge = convex_hull(&[
+                Point3::new(-x, -y, -z),
+                Point3::new(x, -y, -z),
+                Point3::new(-x, y, -z),
+                Point3::new(x, y, -z),
+                Point3::new(x, -y, z),
+                Point3::new(x, y, z),
+            ])?;
+            Some((Isometry3::identity(), wedge))
+        }
+        ShapeDescriptor::ConvexHull { points } => {
+            let points: Vec<Point3<f32>> = points.iter().map(to_point).collect();
+            Some((Isometry3::identity(), convex_hull(&points)?))
+        }
+        ShapeDescriptor::Heightfield { heights, size } => {
+            Some((get_z_up_pose(), heightfield(heights, *size)?))
+        }
+        ShapeDescriptor::Trimesh { vertices, indices } => {
+            let is_valid = !indices.is_empty()
+                && indices
+                    .iter()
+                    .flatten()
+                    .all(|index| (*index as usize) < vertices.len());
+            if !is_valid {
+                return None;
+            }
+            Some((
+                Isometry3::identity(),
+                SharedShape::trimesh(vertices.iter().map(to_point).collect(), indices.clone()),
+            ))
+        }
+    }
+}
+
+/// The radius of a sphere about the local origin that contains the whole shape, worked out from
+/// the descriptor alone so that it is cheap enough for broad checks.
+pub fn get_bounding_radius(descriptor: &ShapeDescriptor) -> f32 {
+    match descriptor {
+        ShapeDescriptor::Cuboid { half_extents } | ShapeDescriptor::Wedge { half_extents } => {
+            to_point(half_extents).coords.norm()
+        }
+        ShapeDescriptor::Cylinder {
+            half_height,
+            radius,
+        } => half_height.hypot(*radius),
+        ShapeDescriptor::ConvexHull { points } => get_farthest_distance(points),
+        ShapeDescriptor::Trimesh { vertices, .. } => get_farthest_distance(vertices),
+        ShapeDescriptor::Heightfield { heights, size } => {
+            let max_height = heights
+                .iter()
+                .flatten()
+                .map(|h| h.abs())
+                .fold(0.0, f32::max);
+            Vector3::new(size.0 / 2.0, size.1 / 2.0, max_height).norm()
+        }
+    }
+}
+
+fn get_farthest_distance(points: &[(f32, f32, f32)]) -> f32 {
+    points
+        .iter()
+        .map(|point| to_point(point).coords.norm())
+        .fold(0.0, f32::max)
+}
+
+// rapier's cylinders and heightfields are y-up, so they are tipped over onto z
+fn get_z_up_pose() -> Isometry3<f32> {
+    Isometry3::rotation(Vector3::x() * FRAC_PI_2)
+}
+
+// SharedShape::convex_hull panics on degenerate input such as too few or coplanar points
+fn convex_hull(points: &[Point3<f32>]) -> Option<SharedShape> {
+    let (vertices, indices) = transformation::try_convex_hull(points).ok()?;
+    SharedShape::convex_mesh(vertices, &indices)
+}
+
+fn heightfield(heights: &[Vec<f32>], size: (f32, f32)) -> Option<SharedShape> {
+    let rows = heights.len();
+    let columns = heights.first()?.len();
+    if rows < 2 || columns < 2 || heights.iter().any(|row| row.len() != columns) {
+        return None;
+    }
+
+    // rapier lays rows out along its z axis, which the z-up pose turns into -y
+    let matrix = DMatrix::from_fn(rows, columns, |row, column| heights[rows - 1 - row][column]);
+    Some(SharedShape::heightfield(
+        matrix,
+        Vector3::new(size.0, 1.0, size.1),
+    ))
+}
+
+fn to_point((x, y, z): &(f32, f32, f32)) -> Point3<f32> {
+    Point3::new(*x, *y, *z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_extents(descriptor: &ShapeDescriptor) -> Vector3<f32> {
+        let (position, shape) = to_shared_shape(descriptor).unwrap();
+        shape.compute_aabb(&position).half_extents()
+    }
+
+    #[test]
+    fn cylinders_stand_along_z() {
+        let extents = get_extents(&ShapeDescriptor::Cylinder {
+            half_height: 3.0,
+            radius: 1.0,
+        });
+
+        assert!((extents.z - 3.0).abs() < 1e-4);
+        assert!((extents.x - 1.0).abs() < 1e-4);
+        assert!((extents.y - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn heightfields_are_rapier_heightfields_rising_along_z() {
+        let descriptor = ShapeDescriptor::Heightfield {
+            heights: vec![vec![0.0, 0.0, 0.0], vec![0.0, 4.0, 0.0]],
+            size: (10.0, 6.0),
+        };
+        let (_position, shape) = to_shared_shape(&descriptor).unwrap();
+        let extents = get_extents(&descriptor);
+
+        assert!(shape.as_heightfield().is_some());
+
+        assert!((extents.x - 5.0).abs() < 1e-4);
+        assert!((extents.y - 3.0).abs() < 1e-4);
+        assert!((extents.z - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn invalid_descriptors_have_no_shape() {
+        let descriptors = [
+            ShapeDescriptor::ConvexHull {
+                points: vec![(0.0, 0.0, 0.0), (1.0, 0.0, 0.0)],
+            },
+            ShapeDescriptor::Trimesh {
+                vertices: vec![(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)],
+                indices: vec![[0, 1, 3]],
+            },
+            ShapeDescriptor::Heightfield {
+                heights: vec![vec![0.0, 1.0], vec![0.0]],
+                size: (1.0, 1.0),
+            },
+        ];
+
+        for descriptor in descriptors.iter() {
+            assert!(to_shared_shape(descriptor).is_none(), "{:?}", descriptor);
+        }
+    }
+
+    #[test]
+    fn descriptors_serialize_with_a_type_tag() {
+        let json = serde_json::to_string(&ShapeDescriptor::Wedge {
+            half_extents: (1.0, 2.0, 0.5),
+        })
+        .unwrap();
+
+        assert_eq!(json, r#"{"type":"wedge","half_extents":[1.0,2.0,0.5]}"#);
+    }
+}
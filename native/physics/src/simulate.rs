@@ -0,0 +1,170 @@
+use crate::config::Config;
+use crate::events::GameEvent;
+use crate::game;
+use crate::mode;
+use crate::user_input::{self, UserInput};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// one minute of play at the default tick rate
+pub const DEFAULT_TICKS: u64 = 3600;
+
+/// A line of a simulation script: inputs to apply just before the given tick is stepped. This is
+/// the same shape as the tick lines of a recording, so a recording without its header can be used
+/// as a script.
+#[derive(Deserialize)]
+struct ScriptedTick {
+    tick: u64,
+    #[serde(default)]
+    inputs: Vec<UserInput>,
+}
+
+pub type Script = BTreeMap<u64, Vec<UserInput>>;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub seed: u64,
+    pub ticks: u64,
+    pub match_seconds: f32,
+    // whether the mode decided the match before the tick limit
+    pub finished: bool,
+    pub winning_team_id: Option<String>,
+    pub scores: BTreeMap<String, i32>,
+    pub hits: u32,
+    pub eliminations: u32,
+    pub respawns: u32,
+    pub pickups_collected: u32,
+    pub bodies_removed: u32,
+}
+
+pub fn load_script(path: &str) -> Result<Script, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    read_script(BufReader::new(file))
+}
+
+fn read_script(reader: impl BufRead) -> Result<Script, String> {
+    let mut script = Script::new();
+    for line in reader.lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let scripted: ScriptedTick = serde_json::from_str(&line).map_err(|err| err.to_string())?;
+        script
+            .entry(scripted.tick)
+            .or_default()
+            .extend(scripted.inputs);
+    }
+    Ok(script)
+}
+
+/// Plays a match without waiting between ticks, until the mode declares a result or `max_ticks`
+/// have passed.
+pub fn run(config: &Config, seed: u64, max_ticks: u64, mut script: Script) -> Summary {
+    let (mut game_state, mut game_mode) = mode::start_match(config, seed);
+    let mut summary = Summary {
+        seed,
+        ..Summary::default()
+    };
+
+    while game::get_tick(&game_state) < max_ticks {
+        let next_tick = game::get_tick(&game_state) + 1;
+        if let Some(inputs) = script.remove(&next_tick) {
+            user_input::apply_user_input(&inputs, &mut game_state);
+        }
+        let bot_inputs = game::get_bot_inputs(&mut game_state);
+        user_input::apply_user_input(&bot_inputs, &mut game_state);
+
+        game::step(&mut game_state);
+        game::take_snapshot_requests(&mut game_state);
+        let events = game::take_events(&mut game_state);
+        count_events(&mut summary, &events);
+
+        if let Some(result) = game_mode.update(&game_state, &events) {
+            summary.finished = true;
+            summary.winning_team_id = result.winning_team_id;
+            summary.scores = result.scores;
+            break;
+        }
+    }
+
+    summary.ticks = game::get_tick(&game_state);
+    summary.match_seconds = game::get_elapsed_ms(&game_state) / 1000.0;
+    summary
+}
+
+fn count_events(summary: &mut Summary, events: &[GameEvent]) {
+    for event in events {
+        match event {
+            // obstacles are hit without a team, and only hits on players are of interest here
+            GameEvent::Hit {
+                team_id: Some(_), ..
+            } => summary.hits += 1,
+            GameEvent::Eliminated { .. } => summary.eliminations += 1,
+            GameEvent::Respawned { .. } => summary.respawns += 1,
+            GameEvent::PickupCollected { .. } => summary.pickups_collected += 1,
+            GameEvent::BodyRemoved { .. } => summary.bodies_removed += 1,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fixtures;
+
+    fn parse_script(lines: &[&str]) -> Script {
+        read_script(lines.join("\n").as_bytes()).unwrap()
+    }
+
+    // "b" stands 5m further along the x axis than "a" and shoots back at it
+    fn player(id: &str, team_id: &str, offset: f32) -> String {
+        let (x, y) = fixtures::OPEN_GROUND;
+        fixtures::add_player(id, team_id, (x + offset, y), 3)
+    }
+
+    fn bullet(id: &str) -> String {
+        let (x, y) = fixtures::OPEN_GROUND;
+        fixtures::shoot(id, "b", "blue", (x + 4.0, y, 1.0), (-40.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn a_scripted_match_is_played_to_the_end() {
+        let add_players = format!(
+            r#"{{"tick":1,"inputs":[{},{}]}}"#,
+            player("a", "red", 0.0),
+            player("b", "blue", 5.0)
+        );
+        let shots: Vec<String> = (0..3)
+            .map(|shot| {
+                format!(
+                    r#"{{"tick":{},"inputs":[{}]}}"#,
+                    30 + shot * 20,
+                    bullet(&format!("bullet-{}", shot))
+                )
+            })
+            .collect();
+        let mut lines = vec![add_players.as_str()];
+        lines.extend(shots.iter().map(String::as_str));
+
+        let summary = run(&Config::default(), 1, 600, parse_script(&lines));
+
+        assert_eq!(summary.hits, 3);
+        assert_eq!(summary.eliminations, 1);
+        assert!(summary.finished);
+        assert_eq!(summary.winning_team_id.as_deref(), Some("blue"));
+        assert!(summary.ticks < 600);
+    }
+
+    #[test]
+    fn an_undecided_match_stops_at_the_tick_limit() {
+        let summary = run(&Config::default(), 1, 30, Script::new());
+
+        assert!(!summary.finished);
+        assert_eq!(summary.ticks, 30);
+        assert!((summary.match_seconds - 0.5).abs() < 1e-3);
+    }
+}
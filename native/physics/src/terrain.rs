@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FloorConfig {
+    #[default]
+    Flat,
+    Heightfield(TerrainConfig),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+    pub seed: u64,
+    // the terrain ranges from -amplitude to +amplitude metres
+    pub amplitude: f32,
+    // distance between the broadest hills, in metres
+    pub wavelength: f32,
+    // number of heightfield cells along each side of the arena
+    pub resolution: usize,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        TerrainConfig {
+            seed: 0,
+            amplitude: 2.0,
+            wavelength: 40.0,
+            resolution: 64,
+        }
+    }
+}
+
+const OCTAVES: u32 = 3;
+
+/// Samples the terrain's height at a point. Octaves of value noise are layered so that broad hills
+/// carry smaller bumps, and the sum is scaled so it stays within the configured amplitude.
+pub fn get_height(config: &TerrainConfig, x: f32, y: f32) -> f32 {
+    let mut height = 0.0;
+    let mut total_weight = 0.0;
+    let mut frequency = 1.0 / config.wavelength.max(f32::EPSILON);
+    let mut weight = 1.0;
+
+    for octave in 0..OCTAVES {
+        let seed = config.seed.wrapping_add(octave as u64);
+        height += weight * value_noise(seed, x * frequency, y * frequency);
+        total_weight += weight;
+        frequency *= 2.0;
+        weight /= 2.0;
+    }
+
+    config.amplitude * height / total_weight
+}
+
+/// Samples the terrain on an evenly spaced square grid centred on the origin. Rows run along y and
+/// columns along x, matching `ShapeDescriptor::Heightfield`.
+pub fn get_heights(config: &TerrainConfig, size: f32) -> Vec<Vec<f32>> {
+    let samples = config.resolution.max(1) + 1;
+    let cell = size / (samples - 1) as f32;
+    let to_coordinate = |index: usize| index as f32 * cell - size / 2.0;
+
+    (0..samples)
+        .map(|row| {
+            (0..samples)
+                .map(|column| get_height(config, to_coordinate(column), to_coordinate(row)))
+                .collect()
+        })
+        .collect()
+}
+
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+    let (ix, iy) = (x0 as i64, y0 as i64);
+
+    let bottom = lerp(
+        get_lattice_value(seed, ix, iy),
+        get_lattice_value(seed, ix + 1, iy),
+        tx,
+    );
+    let top = lerp(
+        get_lattice_value(seed, ix, iy + 1),
+        get_lattice_value(seed, ix + 1, iy + 1),
+        tx,
+    );
+    lerp(bottom, top, ty)
+}
+
+/// A pseudo-random value in [-1, 1] for a lattice point, the same for the same seed on every run.
+fn get_lattice_value(seed: u64, x: i64, y: i64) -> f32 {
+    let mut hash = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    // splitmix64 finaliser
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^= hash >> 31;
+
+    (hash >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{self, ShapeDescriptor};
+    use rapier3d::prelude::{Point, Ray, Vector};
+
+    fn get_config(seed: u64) -> TerrainConfig {
+        TerrainConfig {
+            seed,
+            resolution: 16,
+            ..TerrainConfig::default()
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_terrain() {
+        assert_eq!(
+            get_heights(&get_config(3), 100.0),
+            get_heights(&get_config(3), 100.0)
+        );
+        assert_ne!(
+            get_heights(&get_config(3), 100.0),
+            get_heights(&get_config(4), 100.0)
+        );
+    }
+
+    #[test]
+    fn heights_stay_within_the_amplitude() {
+        let config = get_config(11);
+        let heights = get_heights(&config, 200.0);
+
+        assert!(heights
+            .iter()
+            .flatten()
+            .all(|height| height.abs() <= config.amplitude));
+    }
+
+    #[test]
+    fn the_heightfield_collider_matches_the_sampled_heights() {
+        let config = get_config(5);
+        let size = 100.0;
+        let descriptor = ShapeDescriptor::Heightfield {
+            heights: get_heights(&config, size),
+            size: (size, size),
+        };
+        let (position, heightfield) = shape::to_shared_shape(&descriptor).unwrap();
+
+        // grid points, where the collider's triangles pass exactly through the samples
+        for (x, y) in [(-50.0, -50.0), (-25.0, 12.5), (0.0, 0.0), (37.5, -43.75)] {
+            let ray = Ray::new(Point::new(x, y, 100.0), -Vector::z());
+            let time_of_impact = heightfield.cast_ray(&position, &ray, 200.0, true).unwrap();
+
+            assert!(
+                (100.0 - time_of_impact - get_height(&config, x, y)).abs() < 1e-3,
+                "mismatch at ({}, {})",
+                x,
+                y
+            );
+        }
+    }
+}
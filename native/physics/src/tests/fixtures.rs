@@ -0,0 +1,33 @@
+//! Input lines for the bodies tests set scenes up with, built from the same bodies the Elixir
+//! engine sends so that every test agrees on their size, mass and the rest.
+
+use crate::body;
+use crate::user_input::UserInput;
+
+/// A spot in a corner of the arena. Generated bodies keep the arena margin clear, so a scene staged
+/// here is not in the way of any of them.
+pub const OPEN_GROUND: (f32, f32) = (85.0, 85.0);
+
+fn to_line(input: &UserInput) -> String {
+    serde_json::to_string(input).unwrap()
+}
+
+/// An add_player line for a player standing at `(x, y)`.
+pub fn add_player(id: &str, team_id: &str, (x, y): (f32, f32), hp: i32) -> String {
+    to_line(&UserInput::AddPlayer {
+        body: body::create_player(id, team_id, (x, y, 1.0), hp),
+    })
+}
+
+/// A shoot line for a bullet leaving `from` with the given velocity.
+pub fn shoot(
+    id: &str,
+    owner_id: &str,
+    team_id: &str,
+    from: (f32, f32, f32),
+    linvel: (f32, f32, f32),
+) -> String {
+    to_line(&UserInput::Shoot {
+        body: body::create_bullet(id, owner_id, Some(team_id), from, linvel),
+    })
+}
@@ -0,0 +1,87 @@
+use crate::body::Body;
+use crate::config::Config;
+use crate::events::GameEvent;
+use crate::game::{self, Game};
+use crate::mode::{self, GameMode, MatchResult};
+use crate::user_input;
+
+/// A game in an empty arena, fed input lines the same way the port loop feeds what it reads from
+/// stdin.
+pub struct Harness {
+    game_state: Game,
+    game_mode: Box<dyn GameMode>,
+    pending_lines: Vec<String>,
+    events: Vec<GameEvent>,
+    result: Option<MatchResult>,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Harness::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        let mut game_state = game::init_empty(&config, 0);
+        let mut game_mode = mode::build(&config.mode);
+        game_mode.setup(&mut game_state);
+        Harness {
+            game_state,
+            game_mode,
+            pending_lines: Vec::new(),
+            events: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Queues a line of input to be handled at the start of the next tick.
+    pub fn send(&mut self, line: &str) {
+        self.pending_lines.push(line.to_string());
+    }
+
+    pub fn tick(&mut self) {
+        let inputs: Vec<user_input::UserInput> = self
+            .pending_lines
+            .drain(..)
+            .map(|line| {
+                user_input::parse_user_input(&line)
+                    .unwrap_or_else(|err| panic!("Failed to parse {}: {}", line, err))
+            })
+            .collect();
+        user_input::apply_user_input(&inputs, &mut self.game_state);
+        let bot_inputs = game::get_bot_inputs(&mut self.game_state);
+        user_input::apply_user_input(&bot_inputs, &mut self.game_state);
+
+        game::step(&mut self.game_state);
+        let events = game::take_events(&mut self.game_state);
+        if self.result.is_none() {
+            self.result = self.game_mode.update(&self.game_state, &events);
+        }
+        self.events.extend(events);
+    }
+
+    pub fn run(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    pub fn body(&self, id: &str) -> Option<Body> {
+        game::get_body(&self.game_state, id)
+    }
+
+    /// Every event since the harness was created.
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    pub fn hits_on(&self, id: &str) -> Vec<&GameEvent> {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::Hit { id: hit_id, .. } if hit_id == id))
+            .collect()
+    }
+
+    pub fn result(&self) -> Option<&MatchResult> {
+        self.result.as_ref()
+    }
+}
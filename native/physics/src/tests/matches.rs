@@ -0,0 +1,155 @@
+use super::fixtures::{add_player, shoot};
+use super::harness::Harness;
+use crate::config::Config;
+use crate::events::GameEvent;
+use crate::mode::ModeConfig;
+use crate::rules::{GameRules, RespawnRules};
+
+#[test]
+fn the_last_team_standing_wins() {
+    let mut harness = Harness::new();
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", "blue", (10.0, 0.0), 1));
+    harness.tick();
+    assert!(harness.result().is_none());
+
+    harness.send(&shoot(
+        "bullet-1",
+        "a",
+        "red",
+        (1.0, 0.0, 1.0),
+        (40.0, 0.0, 0.0),
+    ));
+    harness.run(30);
+
+    assert!(harness.events().iter().any(|event| matches!(
+        event,
+        GameEvent::Eliminated { id, attacker_id: Some(attacker_id), .. }
+            if id == "b" && attacker_id == "a"
+    )));
+    let result = harness.result().unwrap();
+    assert_eq!(result.winning_team_id.as_deref(), Some("red"));
+}
+
+#[test]
+fn a_team_deathmatch_is_won_once_a_team_reaches_the_kill_target() {
+    let config = Config {
+        mode: ModeConfig::TeamDeathmatch { kills_to_win: 2 },
+        ..Config::default()
+    };
+    let mut harness = Harness::with_config(config);
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", "blue", (10.0, 0.0), 1));
+    harness.send(&add_player("c", "blue", (0.0, 10.0), 1));
+    harness.tick();
+
+    harness.send(&shoot(
+        "bullet-1",
+        "a",
+        "red",
+        (1.0, 0.0, 1.0),
+        (40.0, 0.0, 0.0),
+    ));
+    harness.run(30);
+    assert!(harness.result().is_none());
+
+    harness.send(&shoot(
+        "bullet-2",
+        "a",
+        "red",
+        (0.0, 1.0, 1.0),
+        (0.0, 40.0, 0.0),
+    ));
+    harness.run(30);
+
+    let result = harness.result().unwrap();
+    assert_eq!(result.winning_team_id.as_deref(), Some("red"));
+    assert_eq!(result.scores.get("red"), Some(&2));
+}
+
+#[test]
+fn a_team_waiting_to_respawn_is_still_in_the_match() {
+    let config = Config {
+        rules: GameRules {
+            respawn: RespawnRules {
+                enabled: true,
+                delay_seconds: 2.0,
+                wave_interval_seconds: None,
+            },
+            ..GameRules::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = Harness::with_config(config);
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", "blue", (10.0, 0.0), 1));
+    harness.tick();
+
+    harness.send(&shoot(
+        "bullet-1",
+        "a",
+        "red",
+        (1.0, 0.0, 1.0),
+        (40.0, 0.0, 0.0),
+    ));
+    harness.run(30);
+    assert_eq!(harness.body("b").unwrap().hp, 0);
+    assert!(harness.result().is_none());
+
+    harness.run(120);
+    assert!(harness.events().iter().any(|event| matches!(
+        event,
+        GameEvent::RespawnCountdown { id, seconds_remaining: 1 } if id == "b"
+    )));
+    assert!(harness
+        .events()
+        .iter()
+        .any(|event| matches!(event, GameEvent::Respawned { id, .. } if id == "b")));
+    assert_eq!(harness.body("b").unwrap().hp, 1);
+    assert!(harness.result().is_none());
+}
+
+fn king_of_the_hill() -> Harness {
+    let config = Config {
+        mode: ModeConfig::KingOfTheHill {
+            center: (0.0, 0.0, 1.0),
+            half_extents: (5.0, 5.0, 2.0),
+            hold_seconds_to_win: 1.0,
+        },
+        ..Config::default()
+    };
+    Harness::with_config(config)
+}
+
+#[test]
+fn the_hill_is_won_by_holding_it_long_enough() {
+    let mut harness = king_of_the_hill();
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", "blue", (20.0, 0.0), 10));
+    harness.run(45);
+    assert!(harness.result().is_none());
+
+    harness.run(30);
+    let result = harness.result().unwrap();
+    assert_eq!(result.winning_team_id.as_deref(), Some("red"));
+    assert_eq!(result.scores.get("red"), Some(&1));
+    assert_eq!(result.scores.get("blue"), None);
+}
+
+#[test]
+fn hold_time_only_adds_up_while_the_hill_is_uncontested() {
+    let mut harness = king_of_the_hill();
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", "blue", (20.0, 0.0), 10));
+    harness.run(30);
+
+    // re-adding a player moves it, here onto the hill and then off it again
+    harness.send(&add_player("b", "blue", (2.0, 0.0), 10));
+    harness.run(90);
+    assert!(harness.result().is_none());
+
+    harness.send(&add_player("b", "blue", (20.0, 0.0), 10));
+    harness.run(45);
+    let result = harness.result().unwrap();
+    assert_eq!(result.winning_team_id.as_deref(), Some("red"));
+}
@@ -0,0 +1,7 @@
+//! Tests that play whole scenes through the input protocol, the way the Elixir server drives the
+//! game, rather than calling into a single module.
+
+pub mod fixtures;
+mod harness;
+mod matches;
+mod shooting;
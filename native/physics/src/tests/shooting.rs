@@ -0,0 +1,167 @@
+use super::fixtures::{add_player, shoot};
+use super::harness::Harness;
+use crate::body::BodyClass;
+use crate::config::Config;
+use crate::events::GameEvent;
+use crate::rules::{FriendlyFire, GameRules, RegionMultipliers};
+
+// "a" shoots along the x axis at whoever stands 10m away
+fn two_players(target_team_id: &str, config: Config) -> Harness {
+    let mut harness = Harness::with_config(config);
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", target_team_id, (10.0, 0.0), 10));
+    harness.tick();
+    harness
+}
+
+fn fire(harness: &mut Harness, bullet_id: &str) {
+    harness.send(&shoot(
+        bullet_id,
+        "a",
+        "red",
+        (1.0, 0.0, 1.0),
+        (40.0, 0.0, 0.0),
+    ));
+}
+
+#[test]
+fn a_shot_adds_a_bullet_owned_by_the_shooter() {
+    let mut harness = two_players("blue", Config::default());
+    fire(&mut harness, "bullet-1");
+    harness.tick();
+
+    let bullet = harness.body("bullet-1").unwrap();
+    assert!(matches!(bullet.class, BodyClass::Bullet));
+    assert_eq!(bullet.owner_id.as_deref(), Some("a"));
+    assert_eq!(bullet.team_id.as_deref(), Some("red"));
+    assert!(bullet.translation.0 > 1.0 && bullet.linvel.0 > 30.0);
+}
+
+#[test]
+fn a_bullet_that_hits_an_enemy_deals_damage_and_is_removed() {
+    let mut harness = two_players("blue", Config::default());
+    fire(&mut harness, "bullet-1");
+    harness.run(30);
+
+    let hits = harness.hits_on("b");
+    assert_eq!(hits.len(), 1);
+    assert!(matches!(
+        hits[0],
+        GameEvent::Hit { attacker_id: Some(attacker_id), hp: 9, .. } if attacker_id == "a"
+    ));
+    assert_eq!(harness.body("b").unwrap().hp, 9);
+    assert!(harness.body("bullet-1").is_none());
+}
+
+#[test]
+fn teammates_are_immune_to_each_others_bullets_by_default() {
+    let mut harness = two_players("red", Config::default());
+    fire(&mut harness, "bullet-1");
+    harness.run(30);
+
+    assert!(harness.hits_on("b").is_empty());
+    assert_eq!(harness.body("b").unwrap().hp, 10);
+}
+
+#[test]
+fn friendly_fire_hurts_teammates_when_turned_on() {
+    let config = Config {
+        rules: GameRules {
+            friendly_fire: FriendlyFire::On,
+            ..GameRules::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = two_players("red", config);
+    fire(&mut harness, "bullet-1");
+    harness.run(30);
+
+    assert_eq!(harness.hits_on("b").len(), 1);
+    assert_eq!(harness.body("b").unwrap().hp, 9);
+}
+
+#[test]
+fn bullets_that_miss_are_removed_once_they_land() {
+    let mut harness = two_players("blue", Config::default());
+    harness.send(&shoot(
+        "bullet-1",
+        "a",
+        "red",
+        (0.0, 1.0, 1.0),
+        (0.0, 20.0, 0.0),
+    ));
+    harness.tick();
+    assert!(harness.body("bullet-1").is_some());
+
+    harness.run(60);
+    assert!(harness.body("bullet-1").is_none());
+    assert!(harness.hits_on("b").is_empty());
+}
+
+#[test]
+fn players_without_ammo_cannot_shoot() {
+    let config = Config {
+        rules: GameRules {
+            starting_ammo: Some(1),
+            ..GameRules::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = two_players("blue", config);
+    fire(&mut harness, "bullet-1");
+    harness.tick();
+    fire(&mut harness, "bullet-2");
+    harness.tick();
+
+    assert!(harness.body("bullet-1").is_some());
+    assert!(harness.body("bullet-2").is_none());
+    assert_eq!(harness.body("a").unwrap().ammo, Some(0));
+}
+
+#[test]
+fn the_hit_region_comes_from_where_a_fast_bullet_struck() {
+    let config = Config {
+        rules: GameRules {
+            region_multipliers: RegionMultipliers {
+                head: 3.0,
+                torso: 1.0,
+                legs: 1.0,
+            },
+            ..GameRules::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = two_players("blue", config);
+    // fast enough to be well past the point of impact by the end of the tick, were it not stopped
+    harness.send(&shoot(
+        "bullet-1",
+        "a",
+        "red",
+        (1.0, 0.0, 1.8),
+        (300.0, 0.0, 0.0),
+    ));
+    harness.run(30);
+
+    assert_eq!(harness.hits_on("b").len(), 1);
+    assert_eq!(harness.body("b").unwrap().hp, 7);
+}
+
+#[test]
+fn re_adding_an_eliminated_player_does_not_bring_it_back() {
+    let mut harness = Harness::new();
+    harness.send(&add_player("a", "red", (0.0, 0.0), 10));
+    harness.send(&add_player("b", "blue", (10.0, 0.0), 1));
+    harness.tick();
+    fire(&mut harness, "bullet-1");
+    harness.run(30);
+    assert_eq!(harness.body("b").unwrap().hp, 0);
+
+    // e.g. the client rejoining
+    harness.send(&add_player("b", "blue", (10.0, 0.0), 10));
+    harness.tick();
+    fire(&mut harness, "bullet-2");
+    harness.run(30);
+
+    assert_eq!(harness.body("b").unwrap().hp, 0);
+    assert_eq!(harness.hits_on("b").len(), 1);
+}
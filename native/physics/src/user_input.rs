@@ -1,27 +1,42 @@
 use rapier3d::prelude::RigidBodyHandle;
 
 use crate::body;
+use crate::bot;
 use crate::game;
+use crossbeam::channel::{self, Receiver};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
 use std::thread;
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "action", rename_all = "snake_case")]
-enum UserInput {
+pub enum UserInput {
     Move {
         id: String,
-        x: f32,
-        y: f32,
+        direction: (f32, f32),
     },
     Rotate {
         id: String,
         rotation_angle: f32,
     },
+    Aim {
+        id: String,
+        yaw: f32,
+        pitch: f32,
+    },
     Jump {
         id: String,
-        linvel_z: f32,
+    },
+    Sprint {
+        id: String,
+        active: bool,
+    },
+    Crouch {
+        id: String,
+        active: bool,
+    },
+    Dash {
+        id: String,
     },
     Shoot {
         #[serde(flatten)]
@@ -31,21 +46,37 @@ enum UserInput {
         #[serde(flatten)]
         body: body::Body,
     },
+    AddBot {
+        team_id: String,
+        #[serde(default)]
+        difficulty: bot::Difficulty,
+    },
+    FindPath {
+        request_id: String,
+        from: (f32, f32, f32),
+        to: (f32, f32, f32),
+    },
+    Explode {
+        center: (f32, f32, f32),
+        radius: f32,
+        impulse: f32,
+    },
+    Snapshot {
+        path: String,
+    },
 }
 type UserInputChannel = Receiver<String>;
 
 pub fn spawn_input_channel() -> Result<UserInputChannel, std::io::Error> {
-    let (tx, rx) = mpsc::channel::<String>();
+    let (tx, rx) = channel::unbounded::<String>();
     let reader = std::io::stdin();
     thread::spawn(move || {
         let mut buf = String::new();
         while reader.read_line(&mut buf).is_ok() {
             let line = buf.trim().to_string();
-            if !line.is_empty() {
-                if tx.send(line).is_err() {
-                    eprintln!("Failed to send user input to channel.");
-                    break;
-                }
+            if !line.is_empty() && tx.send(line).is_err() {
+                eprintln!("Failed to send user input to channel.");
+                break;
             }
             buf.clear();
         }
@@ -53,38 +84,98 @@ pub fn spawn_input_channel() -> Result<UserInputChannel, std::io::Error> {
     Ok(rx)
 }
 
-pub fn handle_user_input(
-    channel: &UserInputChannel,
+/// The number of input lines waiting to be read.
+pub fn get_queue_depth(channel: &UserInputChannel) -> usize {
+    channel.len()
+}
+
+/// Parses the inputs that have arrived since the last tick, skipping any that are malformed.
+pub fn read_user_input(channel: &UserInputChannel) -> Vec<UserInput> {
+    channel
+        .try_iter()
+        .filter_map(|input_line| match parse_user_input(&input_line) {
+            Ok(action) => Some(action),
+            Err(err) => {
+                eprintln!("Failed to parse user input: {}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parses a single line of input as the Elixir server sends it.
+pub fn parse_user_input(input_line: &str) -> Result<UserInput, serde_json::Error> {
+    serde_json::from_str(input_line)
+}
+
+pub fn apply_user_input(
+    inputs: &[UserInput],
     game_state: &mut game::Game,
 ) -> HashSet<RigidBodyHandle> {
     let mut user_updated_handles = HashSet::new();
 
-    for input_line in channel.try_iter() {
-        match serde_json::from_str::<UserInput>(&input_line) {
-            Ok(action) => match action {
-                UserInput::Move { id, x, y } => {
-                    game::move_body(game_state, &id, x, y);
-                    user_updated_handles.insert(game::get_handle(&id, game_state));
-                }
-                UserInput::Rotate { id, rotation_angle } => {
-                    game::rotate_body(game_state, &id, rotation_angle);
-                    user_updated_handles.insert(game::get_handle(&id, game_state));
-                }
-                UserInput::Jump { id, linvel_z } => {
-                    game::jump_body(game_state, &id, linvel_z);
-                    user_updated_handles.insert(game::get_handle(&id, game_state));
-                }
-                UserInput::Shoot { body } => {
-                    game::upsert_body(game_state, &body);
-                    user_updated_handles.insert(game::get_handle(&body.id, game_state));
-                }
-                UserInput::AddPlayer { body } => {
-                    game::upsert_body(game_state, &body);
+    for action in inputs {
+        match action {
+            UserInput::Move { id, direction } => {
+                game::move_body(game_state, id, *direction);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Rotate { id, rotation_angle } => {
+                game::rotate_body(game_state, id, *rotation_angle);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Aim { id, yaw, pitch } => {
+                game::aim_body(game_state, id, *yaw, *pitch);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Jump { id } => {
+                game::jump_body(game_state, id);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Sprint { id, active } => {
+                game::set_sprint(game_state, id, *active);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Crouch { id, active } => {
+                game::set_crouch(game_state, id, *active);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Dash { id } => {
+                game::dash_body(game_state, id);
+                user_updated_handles.insert(game::get_handle(id, game_state));
+            }
+            UserInput::Shoot { body } => {
+                if game::shoot(game_state, body) {
                     user_updated_handles.insert(game::get_handle(&body.id, game_state));
                 }
-            },
-            Err(err) => {
-                eprintln!("Failed to parse user input: {}", err);
+            }
+            UserInput::AddPlayer { body } => {
+                game::upsert_body(game_state, body);
+                user_updated_handles.insert(game::get_handle(&body.id, game_state));
+            }
+            UserInput::AddBot {
+                team_id,
+                difficulty,
+            } => {
+                let handle = game::add_bot(game_state, team_id.clone(), *difficulty);
+                user_updated_handles.insert(handle);
+            }
+            UserInput::FindPath {
+                request_id,
+                from,
+                to,
+            } => {
+                game::report_path(game_state, request_id.clone(), *from, *to);
+            }
+            UserInput::Explode {
+                center,
+                radius,
+                impulse,
+            } => {
+                user_updated_handles.extend(game::explode(game_state, *center, *radius, *impulse));
+            }
+            UserInput::Snapshot { path } => {
+                game::request_snapshot(game_state, path.clone());
             }
         }
     }
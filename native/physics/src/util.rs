@@ -3,3 +3,34 @@ use rapier3d::na::Vector3;
 pub fn to_vec3<T>((x, y, z): (T, T, T)) -> Vector3<T> {
     Vector3::new(x, y, z)
 }
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit FNV-1a hasher. Unlike `DefaultHasher` its output is fixed, so hashes can be written to
+/// files and compared by other builds.
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Hashes a float rounded to a multiple of `step`, so that noise in the last few bits does
+    /// not change the hash.
+    pub fn write_quantised(&mut self, value: f32, step: f32) {
+        self.write(&((value / step).round() as i64).to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
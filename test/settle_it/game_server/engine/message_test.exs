@@ -16,5 +16,29 @@ defmodule SettleIt.GameServer.Engine.MessageTest do
       assert Map.has_key?(bodies, "1")
       assert bodies["1"].class == "player"
     end
+
+    test "decodes the aim, movement, buffs and ammo reported for players" do
+      msg =
+        "[{\"id\":\"1\",\"class\":\"player\",\"translation\":[0,1,2],\"rotation\":[0,0,90],\"linvel\":[0,0,0],\"angvel\":[0,0,0],\"dimensions\":[0,0.525,2],\"mass\":100,\"hp\":10,\"ammo\":5,\"buffs\":[{\"buff\":\"speed\",\"multiplier\":1.5,\"seconds_remaining\":3.0}],\"movement\":{\"sprinting\":true,\"crouching\":false,\"stamina\":80.0,\"dash_cooldown_seconds\":0.0},\"aim\":{\"yaw\":90.0,\"pitch\":-30.0}}]"
+
+      assert {%{"1" => body}, _extra} = Message.decode(msg)
+
+      assert body.aim == %{"yaw" => 90.0, "pitch" => -30.0}
+      assert body.movement["sprinting"] == true
+      assert [%{"buff" => "speed"}] = body.buffs
+      assert body.ammo == 5
+    end
+
+    test "marks bodies removed from the world" do
+      msg = "{\"event\":\"body_removed\",\"id\":\"crate\",\"removed_by\":\"1\"}\n"
+
+      assert {%{"crate" => :removed}, _extra} = Message.decode(msg)
+    end
+
+    test "skips game event messages" do
+      msg = "{\"event\":\"hit\",\"id\":\"1\",\"hp\":9}\n\"game_won\"\n"
+
+      assert Message.decode(msg) == :game_won
+    end
   end
 end
@@ -0,0 +1,35 @@
+defmodule SettleIt.GameServer.StateProducerTest do
+  use ExUnit.Case, async: true
+  alias SettleIt.GameServer.State
+  alias SettleIt.GameServer.StateProducer
+
+  defp obstacle(id) do
+    %State.Body{id: id, class: "obstacle", hp: 20}
+  end
+
+  describe "handle_info/2 with physics data" do
+    test "drops obstacles removed from the world" do
+      state = %State.Game{
+        status: :playing,
+        world_bodies: %{"crate" => obstacle("crate"), "wall" => obstacle("wall")}
+      }
+
+      data = "{\"event\":\"body_removed\",\"id\":\"crate\",\"removed_by\":\"1\"}\n"
+
+      assert {:noreply, [{:state_update, next_state}], next_state} =
+               StateProducer.handle_info({self(), {:data, data}}, state)
+
+      assert Map.keys(next_state.world_bodies) == ["wall"]
+    end
+
+    test "ignores other game events" do
+      state = %State.Game{status: :playing, world_bodies: %{"crate" => obstacle("crate")}}
+      data = "{\"event\":\"hit\",\"id\":\"crate\",\"hp\":19}\n"
+
+      assert {:noreply, [], next_state} =
+               StateProducer.handle_info({self(), {:data, data}}, state)
+
+      assert Map.keys(next_state.world_bodies) == ["crate"]
+    end
+  end
+end
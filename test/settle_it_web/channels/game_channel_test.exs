@@ -15,6 +15,33 @@ defmodule SettleItWeb.GameChannelTest do
     assert socket.assigns.game_id == "123"
   end
 
+  test "player_move_direction takes a numeric direction", %{socket: socket} do
+    ref =
+      push(socket, "player_move_direction", %{
+        "player_id" => "player1",
+        "dx" => 0.6,
+        "dy" => -0.8
+      })
+
+    refute_reply ref, _status
+
+    ref = push(socket, "player_move_direction", %{"player_id" => "player1", "dx" => "left"})
+    assert_reply ref, :error, %{reason: "dx and dy must be numbers"}
+  end
+
+  test "player_move with a position is rejected", %{socket: socket} do
+    ref = push(socket, "player_move", %{"player_id" => "player1", "x" => 10, "y" => 20})
+    assert_reply ref, :error, %{reason: "player_move was replaced by player_move_direction"}
+  end
+
+  test "player_aim takes a numeric yaw and pitch", %{socket: socket} do
+    ref = push(socket, "player_aim", %{"player_id" => "player1", "yaw" => 90, "pitch" => -30.5})
+    refute_reply ref, _status
+
+    ref = push(socket, "player_aim", %{"player_id" => "player1", "yaw" => 90})
+    assert_reply ref, :error, %{reason: "yaw and pitch must be numbers"}
+  end
+
   test "handle_info game_updated event", %{socket: socket} do
     send(socket.channel_pid, {:game_updated, %{status: "running"}})
     assert_push "game:updated", %{status: "running"}